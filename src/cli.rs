@@ -0,0 +1,347 @@
+use std::{fmt::Display, path::PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    config::KnownHostsPolicy,
+    output::OutputFormat,
+    selector::{NodeSelector, parse_node_selector},
+};
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Control the nodes and the fan of a nanocluster board"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Nodes to operate on: slot numbers and ranges, hostnames, model=<model>, label=<key>[=<value>], group=<name>, 'all', '!' to exclude; comma separated. Defaults to all nodes
+    #[arg(long = "node", global = true, value_parser = parse_node_selector)]
+    pub node: Option<NodeSelector>,
+
+    /// Output format of command results
+    #[arg(
+        long = "output",
+        short = 'o',
+        global = true,
+        value_enum,
+        default_value = "table"
+    )]
+    pub output: OutputFormat,
+
+    /// Same as --output json, kept for existing scripts
+    #[arg(long = "json", global = true, hide = true)]
+    pub json: bool,
+
+    /// Configuration file to use instead of the default one
+    #[arg(long = "config", global = true, env = "NANOCLUSTER_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Host key policy for SSH connections, overriding the configuration
+    #[arg(long = "known-hosts", global = true, value_enum)]
+    pub known_hosts: Option<KnownHostsPolicy>,
+
+    /// Cluster context to operate on instead of the file's current_context
+    #[arg(long = "context", global = true, env = "NANOCLUSTER_CONTEXT")]
+    pub context: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Power the selected nodes on, off or through a full cycle, or report their state
+    #[command(subcommand)]
+    Power(PowerCommand),
+    /// Control the controller's fan
+    #[command(subcommand)]
+    Fan(FanCommand),
+    /// Run until Ctrl-C, forcing the fan to full speed on warning temperatures and shutting down nodes above critical ones
+    #[command(alias = "PROTECT")]
+    Protect,
+    /// Full-screen dashboard of the board with power, fan and event log, and keys to act on slots
+    #[command(alias = "tui")]
+    Dashboard {
+        /// Seconds between probes of the nodes and the fan
+        #[arg(long = "refresh", default_value = "5", value_parser = clap::value_parser!(u64).range(1..))]
+        refresh: u64,
+    },
+    /// Collect hardware facts of the selected nodes, store them and report what changed since the last run
+    Inventory,
+    /// Run a shell command on the selected nodes that are on
+    Exec {
+        /// Nodes to run the command on at the same time
+        #[arg(long = "parallel", short = 'j', default_value = "8", value_parser = clap::value_parser!(u64).range(1..))]
+        parallel: u64,
+
+        /// Command and arguments, after `--`
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Copy a local file to the selected nodes that are on
+    Push(PushArgs),
+    /// Copy a file from the selected nodes that are on into a local directory
+    Pull(PullArgs),
+    /// Gather logs and diagnostics of the selected nodes that are on and of the controller into a tar archive
+    Collect {
+        /// File to copy from every node, may be repeated
+        #[arg(long = "file", default_values = crate::commands::collect::DEFAULT_FILES)]
+        files: Vec<String>,
+
+        /// Directory to write the archive to
+        #[arg(long = "directory", default_value = ".")]
+        directory: PathBuf,
+    },
+    /// Find compute modules on the controller's subnet and offer to add them to the configuration
+    Discover,
+    /// Blink an LED of the selected nodes to find them on the board
+    Identify {
+        /// Seconds to blink for, Ctrl-C stops earlier
+        #[arg(long = "duration", default_value = "30")]
+        duration: u64,
+    },
+    /// Find which configured host sits in each slot by powering the slots one at a time, and correct the slot numbers
+    Calibrate(CalibrateArgs),
+    /// Inspect and edit the configuration file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Inspect the configured nodes and manage their host keys
+    #[command(subcommand)]
+    Node(NodeCommand),
+
+    // Commands of earlier releases, kept working for existing scripts.
+    #[command(hide = true, alias = "BOOT")]
+    Boot,
+    #[command(hide = true, alias = "SHUTDOWN")]
+    Shutdown,
+    #[command(hide = true, alias = "STATUS")]
+    Status(StatusArgs),
+    #[command(hide = true, name = "fanmode", alias = "FANMODE")]
+    LegacyFanMode {
+        #[arg(long = "fan-mode", value_enum)]
+        fan_mode: FanMode,
+    },
+    #[command(hide = true, name = "fanspeed", alias = "FANSPEED")]
+    LegacyFanSpeed {
+        #[arg(long = "fan-speed", value_parser = parse_fan_speed)]
+        fan_speed: FanSpeed,
+    },
+    #[command(hide = true, name = "fanstatus", alias = "FANSTATUS")]
+    LegacyFanStatus,
+    #[command(hide = true, name = "fanauto", alias = "FANAUTO")]
+    LegacyFanAuto,
+    #[command(hide = true, alias = "VALIDATE")]
+    Validate,
+    #[command(hide = true, alias = "INIT")]
+    Init,
+    #[command(hide = true, alias = "CONTEXTS")]
+    Contexts,
+    #[command(hide = true, name = "usecontext", alias = "USECONTEXT")]
+    LegacyUseContext,
+    #[command(hide = true, alias = "TRUST")]
+    Trust,
+}
+
+#[derive(Subcommand)]
+pub enum PowerCommand {
+    /// Boot the selected nodes that are off
+    On,
+    /// Shut down the selected nodes that are on. Requires --node; use --node all for every node
+    Off,
+    /// Shut down the selected nodes, wait for them to go off, then boot them again. Requires --node
+    Cycle,
+    /// Reboot the OS of the selected nodes that are on. Requires --node
+    Reboot,
+    /// Print the power state of the selected nodes
+    Status(StatusArgs),
+}
+
+#[derive(Args)]
+pub struct StatusArgs {
+    /// Keep probing the nodes and redraw the table in place, or print transitions as NDJSON with --output json
+    #[arg(long = "watch")]
+    pub watch: bool,
+
+    /// Also read uptime, load, memory, temperature, throttling, kernel and OS release over SSH
+    #[arg(long = "details", conflicts_with = "watch")]
+    pub details: bool,
+
+    /// Seconds between probes in watch mode
+    #[arg(long = "interval", default_value = "5", requires = "watch", value_parser = clap::value_parser!(u64).range(1..))]
+    pub interval: u64,
+}
+
+#[derive(Args)]
+pub struct CalibrateArgs {
+    /// Seconds to wait for a host to answer after powering a slot
+    #[arg(long = "timeout", default_value = "120")]
+    pub timeout: u64,
+
+    /// Write the corrected slot numbers to the configuration file without asking
+    #[arg(long = "write")]
+    pub write: bool,
+
+    /// Start without asking for confirmation
+    #[arg(long = "yes")]
+    pub yes: bool,
+}
+
+#[derive(Args)]
+pub struct PushArgs {
+    /// File to copy
+    pub local: PathBuf,
+
+    /// Destination path on the nodes, a trailing `/` keeps the local file name
+    pub remote: String,
+
+    /// Octal mode of the copied file, the local file's mode by default
+    #[arg(long = "mode")]
+    pub mode: Option<String>,
+
+    /// `user[:group]` to give the copied file to, implies --sudo
+    #[arg(long = "owner")]
+    pub owner: Option<String>,
+
+    /// Write the file with sudo
+    #[arg(long = "sudo")]
+    pub sudo: bool,
+
+    /// Nodes to copy to at the same time
+    #[arg(long = "parallel", short = 'j', default_value = "8", value_parser = clap::value_parser!(u64).range(1..))]
+    pub parallel: u64,
+}
+
+#[derive(Args)]
+pub struct PullArgs {
+    /// File to copy from the nodes
+    pub remote: String,
+
+    /// Directory to copy into, as `<local>/<hostname>/<file name>`
+    pub local: PathBuf,
+
+    /// Read the file with sudo
+    #[arg(long = "sudo")]
+    pub sudo: bool,
+
+    /// Nodes to copy from at the same time
+    #[arg(long = "parallel", short = 'j', default_value = "8", value_parser = clap::value_parser!(u64).range(1..))]
+    pub parallel: u64,
+}
+
+#[derive(Subcommand)]
+pub enum FanCommand {
+    /// Enable or disable the LPI3H's built-in automatic fan control
+    Mode {
+        #[arg(value_enum)]
+        mode: FanMode,
+    },
+    /// Set the fan speed (0 to the cooling device max_state). Requires the fan mode to be disabled
+    Speed {
+        #[arg(value_parser = parse_fan_speed)]
+        speed: FanSpeed,
+    },
+    /// Print the fan mode, cooling state, zone temperature and trip points
+    Status,
+    /// Run until Ctrl-C, driving the fan from the hottest selected node
+    Auto,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Check the configuration file and report every problem with its line and column
+    Validate,
+    /// Interactively write the configuration of the selected context
+    Init,
+    /// List the clusters defined in the configuration file
+    Contexts,
+    /// Make a context the default cluster
+    UseContext {
+        /// Name of the context
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum NodeCommand {
+    /// List the configured nodes with their addresses, groups and labels
+    List,
+    /// Fetch the host keys of the selected nodes and pin them, replacing the old ones. Requires --node
+    Trust,
+}
+
+impl Cli {
+    pub fn output_format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.output
+        }
+    }
+}
+
+impl Command {
+    /// Whether the command acts on node power, and must not run on an invalid configuration.
+    pub fn is_power_action(&self) -> bool {
+        matches!(
+            self,
+            Command::Power(
+                PowerCommand::On | PowerCommand::Off | PowerCommand::Cycle | PowerCommand::Reboot
+            ) | Command::Protect
+                | Command::Dashboard { .. }
+                | Command::Calibrate(_)
+                | Command::Boot
+                | Command::Shutdown
+        )
+    }
+
+    /// Name of the command if it needs an explicit `--node`, because acting on every node
+    /// by default would be dangerous or pointless.
+    pub fn requires_node(&self) -> Option<&'static str> {
+        match self {
+            Command::Power(PowerCommand::Off) => Some("power off"),
+            Command::Power(PowerCommand::Cycle) => Some("power cycle"),
+            Command::Power(PowerCommand::Reboot) => Some("power reboot"),
+            Command::Node(NodeCommand::Trust) | Command::Trust => Some("node trust"),
+            Command::Identify { .. } => Some("identify"),
+            Command::Exec { .. } => Some("exec"),
+            Command::Push(_) => Some("push"),
+            Command::Pull(_) => Some("pull"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum FanMode {
+    Disabled,
+    Enabled,
+}
+
+impl Display for FanMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FanMode::Disabled => write!(f, "disabled"),
+            FanMode::Enabled => write!(f, "enabled"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FanSpeed(pub i32);
+
+impl Display for FanSpeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn parse_fan_speed(s: &str) -> Result<FanSpeed, String> {
+    let speed = s
+        .parse::<i32>()
+        .map_err(|_| format!("Invalid fan speed: {}", s))?;
+    if speed < 0 {
+        Err(format!("Fan speed must not be negative, got {}", speed))
+    } else {
+        Ok(FanSpeed(speed))
+    }
+}
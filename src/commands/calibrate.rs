@@ -0,0 +1,198 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+    path::Path,
+    time::Duration,
+};
+
+use futures::future::join_all;
+use tokio::time::{Instant, sleep};
+
+use crate::{
+    cli::CalibrateArgs,
+    commands::power,
+    config::{self, Config, Model, Node, SLOT_COUNT},
+};
+
+/// Finds which configured host sits in each slot by powering the slots one at a time.
+///
+/// Every worker node must be off. For each slot from 2 up, the slot's power line is pulsed,
+/// which boots a CM4 or a CM5, and the configured host that starts answering is taken to
+/// sit in that slot. It is shut down again before moving on; a slot where nothing answers
+/// has its line pulled low. A host answering before its slot was pulsed stops the run. The
+/// corrected `slot_number`s are then proposed, and written to the context `context` of
+/// `config_file` if accepted.
+pub async fn calibrate(
+    config: &Config,
+    config_file: &Path,
+    content: &str,
+    context: &str,
+    legacy: bool,
+    args: &CalibrateArgs,
+) -> anyhow::Result<()> {
+    let workers: Vec<&Node> = config
+        .cluster
+        .nodes
+        .iter()
+        .filter(|n| n.slot_number != 1)
+        .collect();
+    let running: Vec<&str> = answering(config, &workers)
+        .await
+        .iter()
+        .map(|n| n.hostname.as_str())
+        .collect();
+    if !running.is_empty() {
+        anyhow::bail!(
+            "Calibration needs every worker node off, still on: {}. Run `power off --node '!1'` first",
+            running.join(", ")
+        );
+    }
+    if !args.yes
+        && !confirm("Every slot from 2 to 7 will be powered on and shut down in turn. Continue?")?
+    {
+        return Ok(());
+    }
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut found: BTreeMap<i32, &Node> = BTreeMap::new();
+    for slot in 2..=SLOT_COUNT {
+        let remaining: Vec<&Node> = workers
+            .iter()
+            .copied()
+            .filter(|n| !found.values().any(|f| f.hostname == n.hostname))
+            .collect();
+        if remaining.is_empty() {
+            break;
+        }
+
+        // A host answering before the pulse was powered by an earlier slot, it cannot be
+        // attributed to this one.
+        if let Some(node) = answering(config, &remaining).await.first() {
+            anyhow::bail!(
+                "{} answered before slot {slot} was powered, it is booting slowly from an \
+                 earlier slot; stopping the calibration, run it again with a longer --timeout",
+                node.hostname
+            );
+        }
+
+        println!("Slot {slot}: pulsing the power line...");
+        power::cm5_short_push_power_button(config, &slot).await?;
+        let Some(node) = wait_for_any(config, &remaining, timeout).await? else {
+            println!(
+                "Slot {slot}: no configured host answered within {}s, cutting its power",
+                timeout.as_secs()
+            );
+            // A module still booting, or one missing from the configuration, must not
+            // answer while the next slot is probed.
+            power::cm4_power_off_button(config, &slot).await?;
+            continue;
+        };
+        println!("Slot {slot}: {} ({:?}) answered", node.hostname, node.model);
+        found.insert(slot, node);
+
+        // Shut it down through the slot that was just found, not the configured one.
+        power::send_ssh_shutdown_command(config, node).await?;
+        if !power::wait_for_power_off(config, node).await {
+            anyhow::bail!(
+                "{} did not go off after shutdown, stopping the calibration",
+                node.hostname
+            );
+        }
+        if node.model == Model::CM4 {
+            power::cm4_power_off_button(config, &slot).await?;
+        }
+    }
+
+    let mut changes = Vec::new();
+    println!();
+    for (index, node) in config.cluster.nodes.iter().enumerate() {
+        if node.slot_number == 1 {
+            continue;
+        }
+        match found.iter().find(|(_, f)| f.hostname == node.hostname) {
+            Some((&slot, _)) if slot == node.slot_number => {
+                println!("{}: slot {slot}, as configured", node.hostname)
+            }
+            Some((&slot, _)) => {
+                println!(
+                    "{}: configured in slot {}, found in slot {slot}",
+                    node.hostname, node.slot_number
+                );
+                changes.push((index, slot));
+            }
+            None => println!(
+                "{}: not found, keeping slot {}",
+                node.hostname, node.slot_number
+            ),
+        }
+    }
+    if changes.is_empty() {
+        println!("The slot mapping matches the configuration");
+        return Ok(());
+    }
+
+    let corrected = config::with_slot_numbers(content, context, legacy, &changes)?;
+    let file = config::parse(config_file, &corrected)?;
+    let diagnostics = config::validate(&file, context, config_file, &corrected);
+    if !diagnostics.is_empty() {
+        for diagnostic in &diagnostics {
+            eprintln!("{diagnostic}");
+        }
+        anyhow::bail!("The corrected mapping is not a valid configuration, not writing it");
+    }
+    if args.write
+        || confirm(&format!(
+            "Write the corrected mapping to {}?",
+            config_file.display()
+        ))?
+    {
+        std::fs::write(config_file, corrected)?;
+        println!("Updated {}", config_file.display());
+    }
+    Ok(())
+}
+
+/// Polls `nodes` until one of them answers, returning it. Several hosts answering at once
+/// cannot be told apart and is an error.
+async fn wait_for_any<'a>(
+    config: &Config,
+    nodes: &[&'a Node],
+    timeout: Duration,
+) -> anyhow::Result<Option<&'a Node>> {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        match answering(config, nodes).await.as_slice() {
+            [] => sleep(Duration::from_secs(2)).await,
+            [node] => return Ok(Some(node)),
+            several => anyhow::bail!(
+                "{} answered at the same time, stopping the calibration",
+                several
+                    .iter()
+                    .map(|n| n.hostname.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+    Ok(None)
+}
+
+/// The nodes among `nodes` that answer right now.
+async fn answering<'a>(config: &Config, nodes: &[&'a Node]) -> Vec<&'a Node> {
+    join_all(nodes.iter().map(|&node| async move {
+        power::power_status(config, &node.slot_number)
+            .await
+            .then_some(node)
+    }))
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
+}
+fn confirm(question: &str) -> anyhow::Result<bool> {
+    print!("{question} [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
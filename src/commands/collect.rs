@@ -0,0 +1,223 @@
+use std::path::Path;
+
+use chrono::Local;
+use futures::future::join_all;
+use openssh::Session;
+
+use crate::{
+    commands::{fan, power},
+    config::{Config, Node},
+    output::{NodeResult, OperationReport, Outcome},
+    ssh,
+};
+
+/// Files copied from every node when no `--file` is given.
+pub const DEFAULT_FILES: [&str; 3] = [
+    "/etc/os-release",
+    "/proc/cmdline",
+    "/boot/firmware/config.txt",
+];
+
+/// Commands whose output is saved for every node, by file name. `sudo -n` is tried first
+/// so the full logs are read when passwordless sudo is set up.
+const NODE_COMMANDS: [(&str, &str); 3] = [
+    (
+        "journal-current.log",
+        "sudo -n journalctl -b 0 --no-pager 2>/dev/null || journalctl -b 0 --no-pager",
+    ),
+    (
+        "journal-previous.log",
+        "sudo -n journalctl -b -1 --no-pager 2>/dev/null || journalctl -b -1 --no-pager",
+    ),
+    ("dmesg.log", "sudo -n dmesg 2>/dev/null || dmesg"),
+];
+
+const CONTROLLER_COMMANDS: [(&str, &str); 2] = [
+    (
+        "gpio.txt",
+        "gpioinfo 2>&1; sudo -n cat /sys/kernel/debug/gpio 2>/dev/null; true",
+    ),
+    (
+        "thermal.txt",
+        "grep -H . /sys/class/thermal/*/type /sys/class/thermal/*/temp \
+         /sys/class/thermal/*/mode /sys/class/thermal/*/trip_point_* \
+         /sys/class/thermal/cooling_device*/cur_state \
+         /sys/class/thermal/cooling_device*/max_state 2>/dev/null; true",
+    ),
+];
+
+struct Entry {
+    path: String,
+    content: Vec<u8>,
+}
+
+/// Gathers the journal of the current and previous boot, the kernel log and `files` from
+/// the selected nodes that are on, plus the controller's GPIO and thermal state, into
+/// `<directory>/nanocluster-<context>-<timestamp>.tar`.
+///
+/// Each node's files are under `<hostname>/`, or its address when it has none; what could
+/// not be read is listed in its `errors.txt`.
+pub async fn collect(
+    config: &Config,
+    context: &str,
+    nodes: &[&Node],
+    files: &[String],
+    directory: &Path,
+) -> anyhow::Result<OperationReport> {
+    let name = format!(
+        "nanocluster-{context}-{}",
+        Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let collected = join_all(nodes.iter().map(|&node| async move {
+        if !power::power_status(config, &node.slot_number).await {
+            return (
+                NodeResult::new(node, Outcome::Skipped, "powered off"),
+                vec![],
+            );
+        }
+        match collect_node(config, node, files).await {
+            Ok((entries, errors)) => {
+                let message = if errors.is_empty() {
+                    format!("{} file(s)", entries.len())
+                } else {
+                    format!("{} file(s), {} unreadable", entries.len(), errors.len())
+                };
+                let directory = config.directory_name(node);
+                let entries = with_errors(entries, errors)
+                    .into_iter()
+                    .map(|entry| Entry {
+                        path: format!("{directory}/{}", entry.path),
+                        content: entry.content,
+                    })
+                    .collect();
+                (NodeResult::new(node, Outcome::Ok, message), entries)
+            }
+            Err(e) => (
+                NodeResult::new(node, Outcome::Failed, e.to_string()),
+                vec![],
+            ),
+        }
+    }))
+    .await;
+
+    let mut entries = Vec::new();
+    let mut results = Vec::new();
+    for (result, node_entries) in collected {
+        entries.extend(node_entries);
+        results.push(result);
+    }
+    match collect_controller(config).await {
+        Ok(controller_entries) => entries.extend(controller_entries),
+        Err(e) => log::warn!("Could not read the controller's GPIO and thermal state: {e}"),
+    }
+
+    let archive = directory.join(format!("{name}.tar"));
+    write_archive(&archive, &name, &entries)?;
+    eprintln!("Wrote {}", archive.display());
+    Ok(OperationReport {
+        action: "collect".to_owned(),
+        results,
+    })
+}
+
+/// Returns the node's entries and why the others could not be read.
+async fn collect_node(
+    config: &Config,
+    node: &Node,
+    files: &[String],
+) -> anyhow::Result<(Vec<Entry>, Vec<String>)> {
+    let session = ssh::connect(config, node).await?;
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for (file_name, command) in NODE_COMMANDS {
+        capture(&session, file_name, command, &mut entries, &mut errors).await?;
+    }
+    for file in files {
+        let quoted = ssh::quote(file);
+        let command = format!("sudo -n cat {quoted} 2>/dev/null || cat {quoted}");
+        let path = format!("files/{}", file.trim_start_matches('/'));
+        capture(&session, &path, &command, &mut entries, &mut errors).await?;
+    }
+    Ok((entries, errors))
+}
+
+async fn collect_controller(config: &Config) -> anyhow::Result<Vec<Entry>> {
+    let session = ssh::connect_controller(config).await?;
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for (file_name, command) in CONTROLLER_COMMANDS {
+        capture(&session, file_name, command, &mut entries, &mut errors).await?;
+    }
+    match fan::fan_status(config).await {
+        Ok(status) => entries.push(Entry {
+            path: "fan.json".to_owned(),
+            content: serde_json::to_vec_pretty(&status)?,
+        }),
+        Err(e) => errors.push(format!("fan.json: {e}")),
+    }
+    Ok(with_errors(entries, errors)
+        .into_iter()
+        .map(|entry| Entry {
+            path: format!("controller/{}", entry.path),
+            content: entry.content,
+        })
+        .collect())
+}
+
+/// Adds an `errors.txt` entry listing `errors`, if there is any.
+fn with_errors(mut entries: Vec<Entry>, errors: Vec<String>) -> Vec<Entry> {
+    if !errors.is_empty() {
+        entries.push(Entry {
+            path: "errors.txt".to_owned(),
+            content: (errors.join("\n") + "\n").into_bytes(),
+        });
+    }
+    entries
+}
+
+/// Runs `command` and stores its output as `path`, or records why it failed in `errors`.
+async fn capture(
+    session: &Session,
+    path: &str,
+    command: &str,
+    entries: &mut Vec<Entry>,
+    errors: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    let output = session
+        .command("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .await?;
+    if output.status.success() {
+        entries.push(Entry {
+            path: path.to_owned(),
+            content: output.stdout,
+        });
+    } else {
+        errors.push(format!(
+            "{path}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+fn write_archive(archive: &Path, name: &str, entries: &[Entry]) -> anyhow::Result<()> {
+    let mut builder = tar::Builder::new(std::fs::File::create(archive)?);
+    let mtime = Local::now().timestamp() as u64;
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(entry.content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        builder.append_data(
+            &mut header,
+            format!("{name}/{}", entry.path),
+            entry.content.as_slice(),
+        )?;
+    }
+    builder.finish()?;
+    Ok(())
+}
@@ -0,0 +1,392 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use openssh::Session;
+use serde_derive::Serialize;
+
+use crate::{
+    commands::power,
+    config::{
+        self, Cluster, Config, ConfigFile, DEFAULT_CONTEXT, Diagnostic, Model, Node, SLOT_COUNT,
+        SshSettings,
+    },
+    output::{self, OutputFormat, Row, join_cell},
+    ssh,
+};
+
+/// One problem found by `config validate`.
+#[derive(Debug, Serialize)]
+pub struct DiagnosticRow {
+    pub file: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl Row for DiagnosticRow {
+    const HEADERS: &'static [&'static str] = &["file", "line", "column", "message"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.file.clone(),
+            self.line.map(|l| l.to_string()).unwrap_or_default(),
+            self.column.map(|c| c.to_string()).unwrap_or_default(),
+            self.message.clone(),
+        ]
+    }
+}
+
+/// Prints every problem found in the configuration file, failing if there is any.
+pub fn validate(
+    config_file: &Path,
+    diagnostics: &[Diagnostic],
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    if format != OutputFormat::Table {
+        let rows: Vec<DiagnosticRow> = diagnostics
+            .iter()
+            .map(|d| DiagnosticRow {
+                file: d.file.clone(),
+                line: d.position.map(|(line, _)| line),
+                column: d.position.map(|(_, column)| column),
+                message: d.message.clone(),
+            })
+            .collect();
+        output::print_rows(format, &rows)?;
+    } else if diagnostics.is_empty() {
+        println!("{} is valid", config_file.display());
+    } else {
+        for diagnostic in diagnostics {
+            println!("{diagnostic}");
+        }
+    }
+    if !diagnostics.is_empty() {
+        anyhow::bail!(
+            "{} problem(s) found in {}",
+            diagnostics.len(),
+            config_file.display()
+        );
+    }
+    Ok(())
+}
+
+/// Reads `/proc/device-tree/model` over SSH and returns it with the module it denotes.
+pub async fn detect_model(
+    settings: &SshSettings,
+    hostname: &str,
+) -> anyhow::Result<(String, Option<Model>)> {
+    let session = ssh::connect_address(settings, None, hostname).await?;
+    read_model(&session).await
+}
+
+/// Reads `/proc/device-tree/model` through an open session.
+pub async fn read_model(session: &Session) -> anyhow::Result<(String, Option<Model>)> {
+    let output = session
+        .command("cat")
+        .arg("/proc/device-tree/model")
+        .output()
+        .await?;
+    let model = String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches('\0')
+        .trim()
+        .to_owned();
+    let detected = Model::from_device_tree(&model);
+    Ok((model, detected))
+}
+
+/// One context of the configuration file, as listed by `config contexts`.
+#[derive(Debug, Serialize)]
+pub struct ContextInfo {
+    pub name: String,
+    pub current: bool,
+    pub nodes: usize,
+    pub controller: Option<String>,
+}
+
+impl Row for ContextInfo {
+    const HEADERS: &'static [&'static str] = &["name", "current", "nodes", "controller"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.current.to_string(),
+            self.nodes.to_string(),
+            self.controller.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Prints the contexts of the file, marking the current one.
+pub fn list_contexts(file: &ConfigFile, format: OutputFormat) -> anyhow::Result<()> {
+    let current = file.select(None).ok().map(|(name, _)| name);
+    let contexts: Vec<ContextInfo> = file
+        .contexts
+        .iter()
+        .map(|(name, config)| ContextInfo {
+            name: name.clone(),
+            current: Some(name.as_str()) == current,
+            nodes: config.cluster.nodes.len(),
+            controller: config.controller().ok().map(|n| n.hostname.clone()),
+        })
+        .collect();
+    output::print_rows(format, &contexts)
+}
+
+/// One configured node, as listed by `node list`.
+#[derive(Debug, Serialize)]
+pub struct NodeInfo {
+    pub slot_number: i32,
+    pub hostname: String,
+    pub model: Model,
+    pub addresses: Vec<String>,
+    pub groups: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Row for NodeInfo {
+    const HEADERS: &'static [&'static str] = &[
+        "slot_number",
+        "hostname",
+        "model",
+        "addresses",
+        "groups",
+        "labels",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.slot_number.to_string(),
+            self.hostname.clone(),
+            format!("{:?}", self.model),
+            join_cell(&self.addresses),
+            join_cell(&self.groups),
+            join_cell(self.labels.iter().map(|(k, v)| format!("{k}={v}"))),
+        ]
+    }
+}
+
+/// Prints the configured nodes among `nodes`, without contacting them.
+pub fn list_nodes(config: &Config, nodes: &[&Node], format: OutputFormat) -> anyhow::Result<()> {
+    let nodes: Vec<NodeInfo> = nodes
+        .iter()
+        .map(|node| NodeInfo {
+            slot_number: node.slot_number,
+            hostname: node.hostname.clone(),
+            model: node.model,
+            addresses: config
+                .addresses(node)
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+            groups: config
+                .groups_of(node)
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+            labels: node.labels.clone(),
+        })
+        .collect();
+    output::print_rows(format, &nodes)
+}
+
+/// Makes `name` the context used when `--context` is not given.
+pub fn use_context(
+    config_file: &Path,
+    content: &str,
+    file: &ConfigFile,
+    name: Option<&str>,
+) -> anyhow::Result<()> {
+    let name = name.ok_or_else(|| anyhow::anyhow!("usecontext requires --context <name>"))?;
+    file.select(Some(name))?;
+    if !file.legacy {
+        config::use_context(config_file, content, name)?;
+    }
+    println!("Switched to context {name}");
+    Ok(())
+}
+
+/// Asks for the SSH username and every slot's hostname and model, optionally probing each
+/// host, then writes the resulting configuration once it validates.
+///
+/// The wizard fills the context `context` (or the current one) of `existing`, offering its
+/// values as defaults; other contexts are kept as they are.
+pub async fn init(
+    config_file: &Path,
+    existing: Option<ConfigFile>,
+    context: Option<&str>,
+) -> anyhow::Result<()> {
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+
+    let mut file = existing.unwrap_or(ConfigFile {
+        legacy: true,
+        ..Default::default()
+    });
+    let name = match context {
+        Some(name) => name.to_owned(),
+        None => file
+            .select(None)
+            .map(|(name, _)| name.to_owned())
+            .unwrap_or_else(|_| DEFAULT_CONTEXT.to_owned()),
+    };
+    let existing = file.contexts.remove(&name);
+
+    println!("Configuring context {name} in {}", config_file.display());
+    let existing_node = |slot: i32| {
+        existing
+            .as_ref()
+            .and_then(|c| c.cluster.nodes.iter().find(|n| n.slot_number == slot))
+    };
+
+    let ssh_username = prompt_required(
+        &mut input,
+        "SSH username",
+        existing.as_ref().map(|c| c.ssh_username.as_str()),
+    )?;
+    let probe = !prompt(
+        &mut input,
+        "Probe hosts over SSH to detect their model? [Y/n]",
+        None,
+    )?
+    .eq_ignore_ascii_case("n");
+
+    let probe_settings = SshSettings {
+        user: Some(ssh_username.clone()),
+        known_hosts_file: Some(config::known_hosts_file(config_file)),
+        ..Default::default()
+    };
+
+    let mut nodes = vec![];
+    for slot in 1..=SLOT_COUNT {
+        let previous = existing_node(slot);
+        let previous_hostname = previous.map(|n| n.hostname.as_str());
+        let hostname = if slot == 1 {
+            prompt_required(
+                &mut input,
+                "Controller (slot 1) hostname",
+                previous_hostname,
+            )?
+        } else {
+            prompt(
+                &mut input,
+                &format!("Slot {slot} hostname (empty if unused)"),
+                previous_hostname,
+            )?
+        };
+        if hostname.is_empty() {
+            continue;
+        }
+
+        let mut default_model = previous.map(|n| n.model);
+        if probe {
+            if power::ping(&hostname).await {
+                match detect_model(&probe_settings, &hostname).await {
+                    Ok((raw, Some(model))) => {
+                        println!("  {hostname} is a {model:?} ({raw})");
+                        default_model = Some(model);
+                    }
+                    Ok((raw, None)) => println!("  {hostname} reports an unknown model '{raw}'"),
+                    Err(e) => println!("  Could not read the model of {hostname}: {e}"),
+                }
+            } else {
+                println!("  {hostname} does not answer ping");
+            }
+        }
+        let default_model =
+            default_model.unwrap_or(if slot == 1 { Model::LPI3H } else { Model::CM5 });
+        let model = loop {
+            let answer = prompt(
+                &mut input,
+                &format!("Slot {slot} model (CM4, CM5 or LPI3H)"),
+                Some(&format!("{default_model:?}")),
+            )?;
+            match answer.parse::<Model>() {
+                Ok(model) => break model,
+                Err(e) => println!("  {e}"),
+            }
+        };
+
+        nodes.push(Node {
+            ip_address: previous.map(|n| n.ip_address.clone()).unwrap_or_default(),
+            hostname,
+            address_policy: previous.and_then(|n| n.address_policy),
+            ssh: previous.map(|n| n.ssh.clone()).unwrap_or_default(),
+            model,
+            slot_number: slot,
+            labels: previous.map(|n| n.labels.clone()).unwrap_or_default(),
+        });
+    }
+
+    let mut config = existing.unwrap_or_default();
+    config.ssh_username = ssh_username;
+    config.cluster = Cluster {
+        ip_address: config.cluster.ip_address,
+        address_policy: config.cluster.address_policy,
+        ssh: config.cluster.ssh,
+        groups: config.cluster.groups,
+        nodes,
+    };
+
+    // Keep commands without --context pointed at the cluster they used before.
+    if file.current_context.is_none() && !file.contexts.is_empty() {
+        file.current_context = file.contexts.keys().next().cloned();
+    }
+    file.contexts.insert(name.clone(), config);
+
+    let content = file.to_toml()?;
+    let diagnostics = config::validate(
+        &config::parse(config_file, &content)?,
+        &name,
+        config_file,
+        &content,
+    );
+    if !diagnostics.is_empty() {
+        for diagnostic in &diagnostics {
+            println!("{diagnostic}");
+        }
+        anyhow::bail!("The configuration is invalid, nothing was written");
+    }
+    config::store(config_file, &file)?;
+    println!("Wrote {}", config_file.display());
+    Ok(())
+}
+
+/// Asks `question` and returns the trimmed answer, or `default` when the answer is empty.
+pub fn prompt(
+    input: &mut impl BufRead,
+    question: &str,
+    default: Option<&str>,
+) -> anyhow::Result<String> {
+    match default.filter(|d| !d.is_empty()) {
+        Some(default) => print!("{question} [{default}]: "),
+        None => print!("{question}: "),
+    }
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        anyhow::bail!("Unexpected end of input");
+    }
+    let answer = answer.trim();
+    Ok(if answer.is_empty() {
+        default.unwrap_or_default().to_owned()
+    } else {
+        answer.to_owned()
+    })
+}
+
+fn prompt_required(
+    input: &mut impl BufRead,
+    question: &str,
+    default: Option<&str>,
+) -> anyhow::Result<String> {
+    loop {
+        let answer = prompt(input, question, default)?;
+        if !answer.is_empty() {
+            return Ok(answer);
+        }
+        println!("  A value is required");
+    }
+}
@@ -0,0 +1,530 @@
+use std::{collections::VecDeque, time::Duration};
+
+use chrono::{DateTime, Local};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind};
+use futures::{FutureExt, StreamExt, future::LocalBoxFuture, stream::FuturesUnordered};
+use ratatui::{
+    DefaultTerminal, Frame,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, List, ListItem, Paragraph},
+};
+
+use crate::{
+    cli::{FanMode, FanSpeed},
+    commands::{
+        fan::{self, FanStatus},
+        power::{self, PowerState},
+        telemetry::{self, NodeVitals},
+    },
+    config::{Config, Node, SLOT_COUNT},
+    output::{NodeResult, Outcome},
+};
+
+/// Number of event log entries kept in memory.
+const EVENT_LOG_SIZE: usize = 200;
+
+/// Actions on the selected slot; all but boot ask for confirmation first.
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Boot,
+    Shutdown,
+    Reboot,
+    Cycle,
+}
+
+impl Action {
+    fn name(self) -> &'static str {
+        match self {
+            Action::Boot => "Boot",
+            Action::Shutdown => "Shut down",
+            Action::Reboot => "Reboot",
+            Action::Cycle => "Power-cycle",
+        }
+    }
+}
+
+/// Results of the background work started by the dashboard.
+enum Message {
+    Probed {
+        slot: i32,
+        power: PowerState,
+        vitals: Option<NodeVitals>,
+    },
+    Fan(anyhow::Result<FanStatus>),
+    /// A fan setting was changed, with the status read back afterwards.
+    FanChanged(String, anyhow::Result<FanStatus>),
+    Done(Action, NodeResult),
+}
+
+#[derive(Default)]
+struct SlotView {
+    power: Option<PowerState>,
+    vitals: Option<NodeVitals>,
+    busy: Option<Action>,
+}
+
+struct App<'a> {
+    config: &'a Config,
+    /// Selected slot number, 1 to `SLOT_COUNT`.
+    selected: i32,
+    slots: Vec<SlotView>,
+    fan: Option<FanStatus>,
+    events: VecDeque<(DateTime<Local>, String)>,
+    confirm: Option<Action>,
+    /// Probes still running for the current refresh.
+    probing: usize,
+    /// Whether the fan status of a refresh is still being read.
+    fan_probing: bool,
+}
+
+type Task<'a> = LocalBoxFuture<'a, Message>;
+
+/// Full-screen view of the board: one box per physical slot with the node's power state,
+/// temperature and load, the controller fan, and a log of what happened. Runs until `q`.
+pub async fn dashboard(config: &Config, refresh: Duration) -> anyhow::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, config, refresh).await;
+    ratatui::restore();
+    result
+}
+
+async fn run(
+    terminal: &mut DefaultTerminal,
+    config: &Config,
+    refresh: Duration,
+) -> anyhow::Result<()> {
+    let mut app = App {
+        config,
+        selected: 1,
+        slots: (0..SLOT_COUNT).map(|_| SlotView::default()).collect(),
+        fan: None,
+        events: VecDeque::new(),
+        confirm: None,
+        probing: 0,
+        fan_probing: false,
+    };
+    let mut tasks: FuturesUnordered<Task> = FuturesUnordered::new();
+    let mut input = EventStream::new();
+    let mut ticker = tokio::time::interval(refresh);
+
+    loop {
+        terminal.draw(|frame| draw(frame, &app))?;
+        tokio::select! {
+            _ = ticker.tick() => {
+                // Slow nodes should not pile up probes.
+                if app.probing == 0 {
+                    app.refresh(&mut tasks);
+                }
+            }
+            Some(message) = tasks.next(), if !tasks.is_empty() => app.apply(message, &mut tasks),
+            event = input.next() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                    if !app.handle_key(key, &mut tasks) {
+                        return Ok(());
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+impl<'a> App<'a> {
+    fn node(&self, slot: i32) -> Option<&'a Node> {
+        self.config
+            .cluster
+            .nodes
+            .iter()
+            .find(|n| n.slot_number == slot)
+    }
+
+    /// The view of `slot`, `None` outside 1 to `SLOT_COUNT`.
+    fn slot(&self, slot: i32) -> Option<&SlotView> {
+        self.slots.get(usize::try_from(slot - 1).ok()?)
+    }
+
+    fn slot_mut(&mut self, slot: i32) -> Option<&mut SlotView> {
+        self.slots.get_mut(usize::try_from(slot - 1).ok()?)
+    }
+
+    fn log(&mut self, message: String) {
+        self.events.push_front((Local::now(), message));
+        self.events.truncate(EVENT_LOG_SIZE);
+    }
+
+    fn refresh(&mut self, tasks: &mut FuturesUnordered<Task<'a>>) {
+        let config = self.config;
+        for node in &config.cluster.nodes {
+            if self.slot(node.slot_number).is_none() {
+                continue;
+            }
+            tasks.push(probe(config, node));
+            self.probing += 1;
+        }
+        // A controller slower than the refresh interval should not pile up fan reads either.
+        if !self.fan_probing {
+            self.fan_probing = true;
+            tasks.push(async move { Message::Fan(fan::fan_status(config).await) }.boxed_local());
+        }
+    }
+
+    fn set_fan(&mut self, status: anyhow::Result<FanStatus>) {
+        match status {
+            Ok(status) => self.fan = Some(status),
+            Err(e) => {
+                if self.fan.take().is_some() {
+                    self.log(format!("Cannot read the fan: {e}"));
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, message: Message, tasks: &mut FuturesUnordered<Task<'a>>) {
+        match message {
+            Message::Probed {
+                slot,
+                power,
+                vitals,
+            } => {
+                self.probing = self.probing.saturating_sub(1);
+                let hostname = self.node(slot).map(|n| n.hostname.as_str()).unwrap_or("");
+                let message = format!("Slot {slot} ({hostname}) is now {power:?}");
+                let Some(view) = self.slot_mut(slot) else {
+                    return;
+                };
+                let changed = view.power.is_some_and(|previous| previous != power);
+                view.power = Some(power);
+                view.vitals = vitals;
+                if changed {
+                    self.log(message);
+                }
+            }
+            Message::Fan(status) => {
+                self.fan_probing = false;
+                self.set_fan(status);
+            }
+            Message::FanChanged(message, status) => {
+                self.log(message);
+                self.set_fan(status);
+            }
+            Message::Done(action, result) => {
+                if let Some(view) = self.slot_mut(result.slot_number) {
+                    view.busy = None;
+                }
+                let outcome = match result.outcome {
+                    Outcome::Ok => "done",
+                    Outcome::Skipped => "skipped",
+                    Outcome::Failed => "failed",
+                };
+                self.log(format!(
+                    "{} slot {} ({}): {outcome}, {}",
+                    action.name(),
+                    result.slot_number,
+                    result.hostname,
+                    result.message
+                ));
+                if let Some(node) = self.node(result.slot_number) {
+                    tasks.push(probe(self.config, node));
+                    self.probing += 1;
+                }
+            }
+        }
+    }
+
+    /// Returns false when the dashboard should close.
+    fn handle_key(&mut self, key: KeyEvent, tasks: &mut FuturesUnordered<Task<'a>>) -> bool {
+        if let Some(action) = self.confirm {
+            if let KeyCode::Char('y' | 'Y') = key.code {
+                self.start(action, tasks);
+            }
+            self.confirm = None;
+            return true;
+        }
+        match key.code {
+            // Dropping a running action could leave a node off halfway through a cycle.
+            KeyCode::Char('q') | KeyCode::Esc => match self.running() {
+                Some((slot, action)) => self.log(format!(
+                    "{} slot {slot} is still running, quit once it is done",
+                    action.name()
+                )),
+                None => return false,
+            },
+            KeyCode::Left | KeyCode::Char('h') => {
+                self.selected = (self.selected - 2).rem_euclid(SLOT_COUNT) + 1
+            }
+            KeyCode::Right | KeyCode::Char('l') => self.selected = self.selected % SLOT_COUNT + 1,
+            KeyCode::Char(c @ '1'..='7') => self.selected = c as i32 - '0' as i32,
+            KeyCode::Char('b') => self.start(Action::Boot, tasks),
+            KeyCode::Char('s') => self.ask(Action::Shutdown),
+            KeyCode::Char('r') => self.ask(Action::Reboot),
+            KeyCode::Char('c') => self.ask(Action::Cycle),
+            KeyCode::Char('a') => self.toggle_fan_mode(tasks),
+            KeyCode::Char('+') => self.change_fan_speed(1, tasks),
+            KeyCode::Char('-') => self.change_fan_speed(-1, tasks),
+            KeyCode::Char('u') if self.probing == 0 => self.refresh(tasks),
+            _ => {}
+        }
+        true
+    }
+
+    /// A slot with an action in progress, if any.
+    fn running(&self) -> Option<(i32, Action)> {
+        (1..=SLOT_COUNT).find_map(|slot| Some((slot, self.slot(slot)?.busy?)))
+    }
+
+    fn ask(&mut self, action: Action) {
+        if self.node(self.selected).is_some() {
+            self.confirm = Some(action);
+        }
+    }
+
+    fn start(&mut self, action: Action, tasks: &mut FuturesUnordered<Task<'a>>) {
+        let Some(node) = self.node(self.selected) else {
+            return;
+        };
+        if node.slot_number == 1 {
+            self.log("The controller cannot be powered from here".to_owned());
+            return;
+        }
+        let Some(view) = self.slot_mut(node.slot_number) else {
+            return;
+        };
+        if view.busy.is_some() {
+            self.log(format!("Slot {} is busy", node.slot_number));
+            return;
+        }
+        view.busy = Some(action);
+        self.log(format!(
+            "{} slot {} ({})...",
+            action.name(),
+            node.slot_number,
+            node.hostname
+        ));
+        let config = self.config;
+        tasks.push(
+            async move {
+                let result = match action {
+                    Action::Boot => power::boot_node(config, node).await,
+                    Action::Shutdown => power::shutdown_node(config, node).await,
+                    Action::Reboot => power::reboot_node(config, node).await,
+                    Action::Cycle => power::cycle_node(config, node).await,
+                };
+                Message::Done(action, result)
+            }
+            .boxed_local(),
+        );
+    }
+
+    fn toggle_fan_mode(&mut self, tasks: &mut FuturesUnordered<Task<'a>>) {
+        let Some(status) = &self.fan else {
+            return;
+        };
+        let mode = if status.mode == "enabled" {
+            FanMode::Disabled
+        } else {
+            FanMode::Enabled
+        };
+        let config = self.config;
+        tasks.push(
+            async move {
+                let message = match fan::fan_mode(config, &mode).await {
+                    Ok(()) => format!("Fan mode set to {mode}"),
+                    Err(e) => format!("Cannot set the fan mode: {e}"),
+                };
+                Message::FanChanged(message, fan::fan_status(config).await)
+            }
+            .boxed_local(),
+        );
+    }
+
+    /// Steps the fan state, switching the controller to manual mode first if needed.
+    fn change_fan_speed(&mut self, step: i32, tasks: &mut FuturesUnordered<Task<'a>>) {
+        let Some(status) = &self.fan else {
+            return;
+        };
+        let speed = (status.cur_state + step).clamp(0, status.max_state);
+        let manual = status.mode != "enabled";
+        let config = self.config;
+        tasks.push(
+            async move {
+                let message =
+                    if !manual && let Err(e) = fan::fan_mode(config, &FanMode::Disabled).await {
+                        format!("Cannot switch the fan to manual: {e}")
+                    } else {
+                        match fan::fan_speed(config, &FanSpeed(speed)).await {
+                            Ok(()) => format!("Fan speed set to {speed}"),
+                            Err(e) => format!("Cannot set the fan speed: {e}"),
+                        }
+                    };
+                Message::FanChanged(message, fan::fan_status(config).await)
+            }
+            .boxed_local(),
+        );
+    }
+}
+
+fn probe<'a>(config: &'a Config, node: &'a Node) -> Task<'a> {
+    async move {
+        let power = power::node_status(config, node).await.power;
+        let vitals = match power {
+            PowerState::On => telemetry::read_vitals(config, node).await.ok(),
+            PowerState::Off => None,
+        };
+        Message::Probed {
+            slot: node.slot_number,
+            power,
+            vitals,
+        }
+    }
+    .boxed_local()
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [title, board, fan, log, help] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(8),
+        Constraint::Length(3),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(
+        Line::from(vec![
+            " NanoCluster ".bold().reversed(),
+            Span::raw(format!(
+                " {} nodes, controller {}",
+                app.config.cluster.nodes.len(),
+                app.node(1)
+                    .map(|n| n.hostname.as_str())
+                    .unwrap_or("missing")
+            )),
+        ]),
+        title,
+    );
+
+    let columns =
+        Layout::horizontal((0..SLOT_COUNT).map(|_| Constraint::Ratio(1, SLOT_COUNT as u32)))
+            .split(board);
+    for slot in 1..=SLOT_COUNT {
+        draw_slot(frame, app, slot, columns[(slot - 1) as usize]);
+    }
+
+    let fan_line = match &app.fan {
+        Some(status) => Line::from(vec![
+            Span::raw(format!("mode {}  ", status.mode)),
+            Span::raw(format!("speed {}/{}  ", status.cur_state, status.max_state)),
+            Span::raw(format!("{} {:.1}°C", status.zone_type, status.temperature)),
+        ]),
+        None => Line::from("unknown".dark_gray()),
+    };
+    frame.render_widget(
+        Paragraph::new(fan_line).block(Block::bordered().title(" Fan ")),
+        fan,
+    );
+
+    let items: Vec<ListItem> = app
+        .events
+        .iter()
+        .map(|(time, message)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{} ", time.format("%H:%M:%S")),
+                    Style::new().fg(Color::DarkGray),
+                ),
+                Span::raw(message.clone()),
+            ]))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title(" Events ")),
+        log,
+    );
+
+    frame.render_widget(
+        Line::from(
+            " ←/→ select  b boot  s shut down  r reboot  c power-cycle  a fan auto/manual  +/- fan speed  u refresh  q quit",
+        )
+        .dark_gray(),
+        help,
+    );
+
+    if let Some(action) = app.confirm
+        && let Some(node) = app.node(app.selected)
+    {
+        let area = centered(frame.area(), 50, 5);
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(format!(
+                    "{} slot {} ({})?",
+                    action.name(),
+                    node.slot_number,
+                    node.hostname
+                )),
+                Line::from(""),
+                Line::from("y to confirm, any other key to cancel".dark_gray()),
+            ])
+            .centered()
+            .block(Block::bordered().title(" Confirm ").yellow()),
+            area,
+        );
+    }
+}
+
+fn draw_slot(frame: &mut Frame, app: &App, slot: i32, area: Rect) {
+    let Some(view) = app.slot(slot) else {
+        return;
+    };
+    let mut block = Block::bordered().title(format!(" {slot} "));
+    if slot == app.selected {
+        block = block.border_style(Style::new().yellow().add_modifier(Modifier::BOLD));
+    }
+
+    let lines = match app.node(slot) {
+        None => vec![Line::from("empty".dark_gray())],
+        Some(node) => {
+            let power = match (view.busy, view.power) {
+                (Some(action), _) => Span::raw(format!("{}...", action.name())).yellow(),
+                (None, Some(PowerState::On)) => "on".green().bold(),
+                (None, Some(PowerState::Off)) => "off".red(),
+                (None, None) => "?".dark_gray(),
+            };
+            let vitals = view.vitals.as_ref();
+            let temperature = vitals
+                .and_then(|v| v.temperature)
+                .map(|t| format!("{t:.1}°C"))
+                .unwrap_or_else(|| "-".to_owned());
+            let load = vitals
+                .and_then(|v| v.load)
+                .map(|[one, ..]| format!("load {one:.2}"))
+                .unwrap_or_else(|| "load -".to_owned());
+            let mut role = format!("{:?}", node.model);
+            if slot == 1 {
+                role.push_str(" ctrl");
+            }
+            vec![
+                Line::from(node.hostname.clone()).bold(),
+                Line::from(role),
+                Line::from(power),
+                Line::from(temperature),
+                Line::from(load),
+            ]
+        }
+    };
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
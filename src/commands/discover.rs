@@ -0,0 +1,374 @@
+use std::{collections::BTreeMap, io::IsTerminal, net::Ipv4Addr, path::Path, time::Duration};
+
+use futures::future::join_all;
+use serde_derive::Serialize;
+
+use crate::{
+    commands::config::{prompt, read_model},
+    config::{self, Config, KnownHostsPolicy, Model, Node, SLOT_COUNT},
+    output::{self, OutputFormat, Row},
+    ssh,
+};
+
+/// Raspberry Pi OUIs, with the module generation whose boards carry them when that is
+/// unambiguous. The LPI3H has no fixed OUI and is only recognized by its device tree.
+const OUIS: [(&str, Option<Model>); 7] = [
+    ("b8:27:eb", None),
+    ("dc:a6:32", Some(Model::CM4)),
+    ("e4:5f:01", Some(Model::CM4)),
+    ("28:cd:c1", None),
+    ("d8:3a:dd", None),
+    ("2c:cf:67", Some(Model::CM5)),
+    ("88:a2:9e", None),
+];
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Distributions whose OpenSSH banner, e.g. `SSH-2.0-OpenSSH_9.2p1 Debian-2+deb12u3`, is
+/// what compute modules without a Raspberry Pi OUI usually run.
+const PI_BANNERS: [&str; 3] = ["Raspbian", "Debian", "Ubuntu"];
+
+/// A host found on the controller's subnet.
+#[derive(Debug, Serialize)]
+pub struct DiscoveredHost {
+    pub ip_address: String,
+    pub mac_address: String,
+    /// Whether the MAC address carries a Raspberry Pi OUI.
+    pub raspberry_pi_oui: bool,
+    /// Name resolved over mDNS, e.g. `node-02.local`.
+    pub mdns_name: Option<String>,
+    pub ssh_banner: Option<String>,
+    pub device_tree_model: Option<String>,
+    /// From the device tree, else guessed from the OUI.
+    pub model: Option<Model>,
+    /// Slot of the configured node with this address or name.
+    pub configured_slot: Option<i32>,
+}
+
+impl Row for DiscoveredHost {
+    const HEADERS: &'static [&'static str] = &[
+        "ip_address",
+        "mac_address",
+        "raspberry_pi_oui",
+        "mdns_name",
+        "model",
+        "configured_slot",
+        "device_tree_model",
+        "ssh_banner",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.ip_address.clone(),
+            self.mac_address.clone(),
+            self.raspberry_pi_oui.to_string(),
+            self.mdns_name.clone().unwrap_or_default(),
+            self.model.map(|m| format!("{m:?}")).unwrap_or_default(),
+            self.configured_slot
+                .map(|s| s.to_string())
+                .unwrap_or_default(),
+            self.device_tree_model.clone().unwrap_or_default(),
+            self.ssh_banner.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Scans the controller's subnet for compute modules and prints them. In a terminal, then
+/// offers to add the ones missing from the configuration to the context `context` of
+/// `config_file`.
+pub async fn discover(
+    config: &Config,
+    config_file: &Path,
+    content: &str,
+    context: &str,
+    legacy: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let hosts = scan(config).await?;
+    output::print_rows(format, &hosts)?;
+
+    let new: Vec<&DiscoveredHost> = hosts
+        .iter()
+        .filter(|h| h.configured_slot.is_none() && h.model.is_some())
+        .collect();
+    if new.is_empty() || format != OutputFormat::Table {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        println!(
+            "{} host(s) are not in the configuration, run `discover` in a terminal to add them",
+            new.len()
+        );
+        return Ok(());
+    }
+
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let mut free: Vec<i32> = (2..=SLOT_COUNT)
+        .filter(|slot| config.cluster.nodes.iter().all(|n| n.slot_number != *slot))
+        .collect();
+    let mut nodes = Vec::new();
+    for host in new {
+        if free.is_empty() {
+            println!("Every slot is taken, not adding more nodes");
+            break;
+        }
+        let name = host.mdns_name.as_deref().unwrap_or(&host.ip_address);
+        let model = host.model.expect("only hosts with a model are offered");
+        let free_list: Vec<String> = free.iter().map(i32::to_string).collect();
+        let slot = loop {
+            let answer = prompt(
+                &mut input,
+                &format!(
+                    "Slot of {name} ({model:?}), free: {}, empty to skip",
+                    free_list.join(", ")
+                ),
+                None,
+            )?;
+            if answer.is_empty() {
+                break None;
+            }
+            match answer.parse::<i32>() {
+                Ok(slot) if free.contains(&slot) => break Some(slot),
+                _ => println!("  Expected one of {}", free_list.join(", ")),
+            }
+        };
+        let Some(slot) = slot else {
+            continue;
+        };
+        free.retain(|s| *s != slot);
+        nodes.push(Node {
+            // mDNS names follow the host across DHCP leases, the address does not.
+            ip_address: if host.mdns_name.is_some() {
+                String::new()
+            } else {
+                host.ip_address.clone()
+            },
+            hostname: name.to_owned(),
+            address_policy: None,
+            ssh: Default::default(),
+            model,
+            slot_number: slot,
+            labels: BTreeMap::new(),
+        });
+    }
+    if nodes.is_empty() {
+        return Ok(());
+    }
+
+    let merged = config::with_new_nodes(content, context, legacy, &nodes)?;
+    let diagnostics = config::validate(
+        &config::parse(config_file, &merged)?,
+        context,
+        config_file,
+        &merged,
+    );
+    if !diagnostics.is_empty() {
+        for diagnostic in &diagnostics {
+            eprintln!("{diagnostic}");
+        }
+        anyhow::bail!("The merged configuration is invalid, nothing was written");
+    }
+    std::fs::write(config_file, merged)?;
+    println!("Added {} node(s) to {}", nodes.len(), config_file.display());
+    Ok(())
+}
+
+/// Finds the hosts on the controller's subnet from its neighbor table after a ping sweep,
+/// names them over mDNS, reads their SSH banner, then reads the device tree of the ones
+/// that look like compute modules.
+async fn scan(config: &Config) -> anyhow::Result<Vec<DiscoveredHost>> {
+    let session = ssh::connect_controller(config).await?;
+    let interfaces = ssh::run(&session, "ip -o -4 addr show scope global").await?;
+    let mut targets = Vec::new();
+    let mut names = Vec::new();
+    for line in interfaces.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (Some(name), Some(cidr)) = (fields.get(1), fields.get(3)) else {
+            continue;
+        };
+        let Some((address, prefix)) = cidr.split_once('/') else {
+            continue;
+        };
+        let (Ok(address), Ok(prefix)) = (address.parse::<Ipv4Addr>(), prefix.parse::<u32>()) else {
+            continue;
+        };
+        if prefix < 24 {
+            log::warn!("{name} is on a /{prefix}, only sweeping the /24 around {address}");
+        }
+        names.push(name.to_string());
+        targets.extend(subnet_hosts(address, prefix.max(24)));
+    }
+    if names.is_empty() {
+        anyhow::bail!("The controller has no IPv4 address to scan from");
+    }
+
+    // Pinging every address fills the neighbor table with the hosts that are up.
+    let sweep = targets
+        .iter()
+        .map(Ipv4Addr::to_string)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let neighbors = ssh::run(
+        &session,
+        &format!(
+            "for ip in {sweep}; do ping -c 1 -W 1 $ip >/dev/null 2>&1 & done; wait; ip -4 neigh show"
+        ),
+    )
+    .await?;
+    // `192.168.1.12 dev eth0 lladdr dc:a6:32:01:02:03 REACHABLE`
+    let mut found: BTreeMap<Ipv4Addr, String> = BTreeMap::new();
+    for line in neighbors.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let on_scanned = fields
+            .iter()
+            .position(|f| *f == "dev")
+            .and_then(|i| fields.get(i + 1))
+            .is_some_and(|dev| names.iter().any(|n| n == dev));
+        let mac = fields
+            .iter()
+            .position(|f| *f == "lladdr")
+            .and_then(|i| fields.get(i + 1));
+        if let (Some(Ok(ip)), Some(mac), true) =
+            (fields.first().map(|f| f.parse()), mac, on_scanned)
+            && !line.ends_with("FAILED")
+        {
+            found.insert(ip, mac.to_ascii_lowercase());
+        }
+    }
+
+    let ips = found
+        .keys()
+        .map(Ipv4Addr::to_string)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let probes = ssh::run(
+        &session,
+        &format!(
+            "echo @mdns; command -v avahi-resolve >/dev/null && \
+               for ip in {ips}; do avahi-resolve -4 -a $ip 2>/dev/null & done; wait; \
+             echo @ssh; command -v nc >/dev/null && for ip in {ips}; do \
+               (b=$(timeout 3 nc -w 2 $ip 22 </dev/null 2>/dev/null | head -n 1); \
+               [ -n \"$b\" ] && echo \"$ip $b\") & done; wait; true"
+        ),
+    )
+    .await?;
+    let mut mdns = BTreeMap::new();
+    let mut banners = BTreeMap::new();
+    let mut section = "";
+    for line in probes.lines() {
+        if let Some(name) = line.strip_prefix('@') {
+            section = name;
+        } else if let Some((ip, value)) = line.trim().split_once(char::is_whitespace) {
+            let value = value.trim().to_owned();
+            match section {
+                "mdns" => mdns.insert(ip.to_owned(), value),
+                _ => banners.insert(ip.to_owned(), value),
+            };
+        }
+    }
+
+    // Probed hosts are not trusted nodes yet, keep their keys out of the managed known_hosts.
+    let scratch = std::env::temp_dir().join(format!("nanocluster-discover-{}", std::process::id()));
+    let known_hosts = scratch.join("known_hosts");
+    let known_hosts = &known_hosts;
+    let hosts = join_all(found.into_iter().map(|(ip, mac)| {
+        let ip = ip.to_string();
+        let mdns_name = mdns.get(&ip).cloned();
+        let ssh_banner = banners.get(&ip).cloned();
+        async move {
+            let oui = OUIS.iter().find(|(oui, _)| mac.starts_with(oui));
+            let pi_banner = ssh_banner
+                .as_deref()
+                .is_some_and(|banner| PI_BANNERS.iter().any(|b| banner.contains(b)));
+            let device_tree_model = if oui.is_some() || pi_banner {
+                probe(config, &ip, known_hosts).await
+            } else {
+                None
+            };
+            let model = device_tree_model
+                .as_deref()
+                .and_then(Model::from_device_tree)
+                .or(oui.and_then(|(_, model)| *model));
+            let configured_slot = config
+                .cluster
+                .nodes
+                .iter()
+                .find(|n| {
+                    n.ip_address == ip
+                        || n.hostname == ip
+                        || mdns_name.as_deref().is_some_and(|name| {
+                            n.hostname.eq_ignore_ascii_case(name)
+                                || name
+                                    .strip_suffix(".local")
+                                    .is_some_and(|short| n.hostname.eq_ignore_ascii_case(short))
+                        })
+                })
+                .map(|n| n.slot_number);
+            DiscoveredHost {
+                ip_address: ip,
+                raspberry_pi_oui: oui.is_some(),
+                mac_address: mac,
+                mdns_name,
+                ssh_banner,
+                device_tree_model,
+                model,
+                configured_slot,
+            }
+        }
+    }))
+    .await;
+    if let Err(e) = std::fs::remove_dir_all(&scratch)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        log::debug!("Could not remove {}: {e}", scratch.display());
+    }
+    Ok(hosts
+        .into_iter()
+        .filter(|h| h.model.is_some() || h.raspberry_pi_oui || h.configured_slot.is_some())
+        .collect())
+}
+
+/// Reads the device-tree model of the host at `ip` with the cluster's SSH settings,
+/// accepting its host key into the throwaway `known_hosts` file.
+async fn probe(config: &Config, ip: &str, known_hosts: &Path) -> Option<String> {
+    let node = Node {
+        ip_address: ip.to_owned(),
+        hostname: ip.to_owned(),
+        address_policy: None,
+        ssh: Default::default(),
+        model: Model::CM5,
+        slot_number: 0,
+        labels: BTreeMap::new(),
+    };
+    let mut settings = config.ssh_settings(&node);
+    settings.known_hosts = Some(KnownHostsPolicy::Accept);
+    settings.known_hosts_file = Some(known_hosts.to_owned());
+    let read = async {
+        let jump_host = ssh::jump_host(config, &node, &settings)?;
+        let session = ssh::connect_address(&settings, jump_host.as_deref(), ip).await?;
+        read_model(&session).await
+    };
+    match tokio::time::timeout(PROBE_TIMEOUT, read).await {
+        Ok(Ok((model, _))) => Some(model).filter(|m| !m.is_empty()),
+        Ok(Err(e)) => {
+            log::debug!("Could not read the device tree of {ip}: {e}");
+            None
+        }
+        Err(_) => {
+            log::debug!("Timed out reading the device tree of {ip}");
+            None
+        }
+    }
+}
+
+/// Host addresses of the subnet `address/prefix`, without `address` itself.
+fn subnet_hosts(address: Ipv4Addr, prefix: u32) -> Vec<Ipv4Addr> {
+    let mask = u32::MAX << (32 - prefix.min(30));
+    let network = u32::from(address) & mask;
+    let broadcast = network | !mask;
+    (network + 1..broadcast)
+        .map(Ipv4Addr::from)
+        .filter(|ip| *ip != address)
+        .collect()
+}
@@ -0,0 +1,104 @@
+use futures::{StreamExt, stream};
+use openssh::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+use crate::{
+    commands::power,
+    config::{Config, Node},
+    output::{NodeResult, OperationReport, Outcome, OutputFormat},
+    ssh,
+};
+
+/// Runs `command` through the remote shell of the selected nodes that are on, at most
+/// `parallel` at a time, printing each output line prefixed with the node's hostname.
+///
+/// Output is streamed to stdout and stderr as the node writes it, except with a
+/// structured output format, where all of it goes to stderr to keep stdout parseable.
+pub async fn exec(
+    config: &Config,
+    nodes: &[&Node],
+    command: &[String],
+    parallel: usize,
+    format: OutputFormat,
+) -> OperationReport {
+    let command = command.join(" ");
+    let width = nodes.iter().map(|n| n.hostname.len()).max().unwrap_or(0);
+    let command = &command;
+    let mut results: Vec<NodeResult> = stream::iter(nodes)
+        .map(|&node| async move {
+            if !power::power_status(config, &node.slot_number).await {
+                return NodeResult::new(node, Outcome::Skipped, "powered off");
+            }
+            let prefix = format!("{:<width$} |", node.hostname);
+            match run(config, node, command, &prefix, format).await {
+                Ok(Some(0)) => NodeResult::new(node, Outcome::Ok, "exit code 0"),
+                Ok(Some(code)) => {
+                    NodeResult::new(node, Outcome::Failed, format!("exit code {code}"))
+                }
+                Ok(None) => NodeResult::new(node, Outcome::Failed, "killed by a signal"),
+                Err(e) => NodeResult::new(node, Outcome::Failed, e.to_string()),
+            }
+        })
+        .buffer_unordered(parallel.max(1))
+        .collect()
+        .await;
+    results.sort_by_key(|r| r.slot_number);
+    OperationReport {
+        action: "exec".to_owned(),
+        results,
+    }
+}
+
+/// Returns the exit code of `command`, `None` when it was killed by a signal.
+async fn run(
+    config: &Config,
+    node: &Node,
+    command: &str,
+    prefix: &str,
+    format: OutputFormat,
+) -> anyhow::Result<Option<i32>> {
+    let session = ssh::connect(config, node).await?;
+    let mut child = session
+        .raw_command(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .await?;
+    let stdout = child.stdout().take();
+    let stderr = child.stderr().take();
+    let to_stdout = format == OutputFormat::Table;
+    let forwarded = tokio::join!(
+        forward(stdout, prefix, to_stdout),
+        forward(stderr, prefix, false)
+    );
+    // Reap the command even if its output could not be read.
+    let status = child.wait().await?;
+    forwarded.0?;
+    forwarded.1?;
+    Ok(status.code())
+}
+
+async fn forward(
+    stream: Option<impl AsyncRead + Unpin>,
+    prefix: &str,
+    to_stdout: bool,
+) -> std::io::Result<()> {
+    let Some(stream) = stream else {
+        return Ok(());
+    };
+    // Lines are split on bytes, output that is not UTF-8 is printed lossily.
+    let mut reader = BufReader::new(stream);
+    let mut buffer = Vec::new();
+    while reader.read_until(b'\n', &mut buffer).await? > 0 {
+        let line = String::from_utf8_lossy(&buffer);
+        let line = line.trim_end_matches(['\n', '\r']);
+        if to_stdout {
+            println!("{prefix} {line}");
+        } else {
+            eprintln!("{prefix} {line}");
+        }
+        buffer.clear();
+    }
+    Ok(())
+}
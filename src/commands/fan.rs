@@ -1,57 +1,278 @@
-use openssh::{KnownHosts, Session};
+use std::collections::HashMap;
 
-use crate::{Config, FanMode, FanSpeed};
+use openssh::Session;
+use serde_derive::Serialize;
 
-pub async fn fan_mode(config: &Config, fan_mode: &FanMode) -> anyhow::Result<()> {
-    let username = &config.ssh_username;
-    let cluster_controller_hostname = &config
-        .cluster
-        .nodes
-        .iter()
-        .find(|n| n.slot_number == 1)
-        .unwrap()
-        .hostname;
-    let session = Session::connect(
-        format!("{username}@{cluster_controller_hostname}"),
-        KnownHosts::Add,
-    )
-    .await?;
+use crate::{
+    cli::{FanMode, FanSpeed},
+    config::Config,
+    output::{self, OutputFormat, Row, join_cell},
+    ssh,
+};
+
+const THERMAL_CLASS: &str = "/sys/class/thermal";
+
+/// Sysfs locations of the fan's cooling device and of the thermal zone it is bound to.
+#[derive(Debug)]
+pub struct FanDevice {
+    pub zone: String,
+    pub device: String,
+    pub max_state: i32,
+}
+
+/// Snapshot of the controller's fan and of the thermal zone driving it.
+#[derive(Debug, Serialize)]
+pub struct FanStatus {
+    pub mode: String,
+    pub cur_state: i32,
+    pub max_state: i32,
+    pub device_type: String,
+    pub zone_type: String,
+    /// Zone temperature in degrees Celsius.
+    pub temperature: f64,
+    pub trip_points: Vec<TripPoint>,
+}
+
+/// Fan setting changed by `fan mode` or `fan speed`.
+#[derive(Debug, Serialize)]
+pub struct FanSetting {
+    pub setting: &'static str,
+    pub value: String,
+}
+
+impl Row for FanSetting {
+    const HEADERS: &'static [&'static str] = &["setting", "value"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.setting.to_owned(), self.value.clone()]
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TripPoint {
+    pub index: usize,
+    pub kind: String,
+    /// Trip temperature in degrees Celsius.
+    pub temperature: f64,
+}
+
+/// Enumerates `/sys/class/thermal` on the controller and picks the cooling device and
+/// thermal zone whose `type` match the fan configuration.
+pub async fn discover_fan(session: &Session, config: &Config) -> anyhow::Result<FanDevice> {
+    // Every line is `path:value`; zone bindings are reported as `.../thermal_zoneN/cdevM:cooling_deviceK`.
     let output = session
         .command("sh")
         .arg("-c")
         .arg(format!(
-            "echo {fan_mode} | sudo tee /sys/class/thermal/thermal_zone2/mode"
+            "grep -H . {THERMAL_CLASS}/*/type {THERMAL_CLASS}/cooling_device*/max_state; \
+             for l in {THERMAL_CLASS}/thermal_zone*/cdev[0-9]*; do \
+             [ -L \"$l\" ] && echo \"$l:$(basename $(readlink -f $l))\"; done; true"
         ))
         .output()
-        .await
-        .expect("Failed to execute disable fan command");
-    log::info!("{}", String::from_utf8_lossy(&output.stdout));
+        .await?;
+    let mut entries: Vec<(String, String)> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(path, value)| (path.to_owned(), value.trim().to_owned()))
+        .collect();
+    entries.sort();
+
+    let with_type = |prefix: &str, wanted: &str| {
+        entries.iter().find_map(|(path, value)| {
+            let dir = path.strip_suffix("/type")?;
+            let name = dir.strip_prefix(&format!("{THERMAL_CLASS}/"))?;
+            (name.starts_with(prefix) && value == wanted).then(|| dir.to_owned())
+        })
+    };
+
+    let wanted_device = &config.fan.cooling_device_type;
+    let device = with_type("cooling_device", wanted_device).ok_or_else(|| {
+        anyhow::anyhow!("No cooling device of type '{wanted_device}' found on the controller")
+    })?;
+    let device_name = device.trim_start_matches(&format!("{THERMAL_CLASS}/"));
+
+    let zone = match &config.fan.thermal_zone_type {
+        Some(wanted_zone) => with_type("thermal_zone", wanted_zone).ok_or_else(|| {
+            anyhow::anyhow!("No thermal zone of type '{wanted_zone}' found on the controller")
+        })?,
+        None => entries
+            .iter()
+            .find_map(|(path, value)| {
+                let (zone, link) = path.rsplit_once('/')?;
+                (link.starts_with("cdev") && value == device_name).then(|| zone.to_owned())
+            })
+            .ok_or_else(|| anyhow::anyhow!("No thermal zone is bound to {device_name}"))?,
+    };
+
+    let max_state = entries
+        .iter()
+        .find(|(path, _)| *path == format!("{device}/max_state"))
+        .ok_or_else(|| anyhow::anyhow!("Missing max_state for {device_name}"))?
+        .1
+        .parse()?;
+
+    log::debug!("Using cooling device {device} (max_state {max_state}) and zone {zone}");
+    Ok(FanDevice {
+        zone,
+        device,
+        max_state,
+    })
+}
+
+pub async fn fan_mode(config: &Config, fan_mode: &FanMode) -> anyhow::Result<()> {
+    let session = ssh::connect_controller(config).await?;
+    let fan = discover_fan(&session, config).await?;
+    let output = ssh::run(
+        &session,
+        &format!("echo {fan_mode} | sudo tee {}/mode", fan.zone),
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to set the fan mode: {e}"))?;
+    log::info!("{output}");
     Ok(())
 }
 
 pub async fn fan_speed(config: &Config, fan_speed: &FanSpeed) -> anyhow::Result<()> {
-    let username = &config.ssh_username;
-    let cluster_controller_hostname = &config
-        .cluster
-        .nodes
-        .iter()
-        .find(|n| n.slot_number == 1)
-        .unwrap()
-        .hostname;
-    let session = Session::connect(
-        format!("{username}@{cluster_controller_hostname}"),
-        KnownHosts::Add,
+    let session = ssh::connect_controller(config).await?;
+    let fan = discover_fan(&session, config).await?;
+    if fan_speed.0 > fan.max_state {
+        anyhow::bail!(
+            "Fan speed must be between 0 and {}, got {fan_speed}",
+            fan.max_state
+        );
+    }
+    let output = ssh::run(
+        &session,
+        &format!("echo {fan_speed} | sudo tee {}/cur_state", fan.device),
     )
-    .await?;
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to set the fan speed: {e}"))?;
+    log::info!("{output}");
+    Ok(())
+}
+
+/// Reads the fan mode, cooling device state and thermal zone readings from the controller.
+pub async fn fan_status(config: &Config) -> anyhow::Result<FanStatus> {
+    let session = ssh::connect_controller(config).await?;
+    let FanDevice { zone, device, .. } = discover_fan(&session, config).await?;
+    // `grep -H .` prints every file as `path:value`, which keeps this to a single round trip.
     let output = session
         .command("sh")
         .arg("-c")
         .arg(format!(
-            "echo {fan_speed} | sudo tee /sys/class/thermal/cooling_device0/cur_state"
+            "grep -H . {zone}/mode {zone}/type {zone}/temp \
+             {zone}/trip_point_*_type {zone}/trip_point_*_temp \
+             {device}/type {device}/cur_state {device}/max_state"
         ))
         .output()
-        .await
-        .expect("Failed to execute set fan speed command");
-    log::info!("{}", String::from_utf8_lossy(&output.stdout));
-    Ok(())
+        .await?;
+    if output.stdout.is_empty() {
+        anyhow::bail!(
+            "Failed to read fan status: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let values: HashMap<String, String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(path, value)| (path.to_owned(), value.trim().to_owned()))
+        .collect();
+    let read = |path: String| -> anyhow::Result<&String> {
+        values
+            .get(&path)
+            .ok_or_else(|| anyhow::anyhow!("Missing value for {path}"))
+    };
+
+    let mut trip_points = Vec::new();
+    for index in 0.. {
+        let Some(temp) = values.get(&format!("{zone}/trip_point_{index}_temp")) else {
+            break;
+        };
+        trip_points.push(TripPoint {
+            index,
+            kind: values
+                .get(&format!("{zone}/trip_point_{index}_type"))
+                .cloned()
+                .unwrap_or_default(),
+            temperature: millicelsius(temp)?,
+        });
+    }
+
+    Ok(FanStatus {
+        mode: read(format!("{zone}/mode"))?.clone(),
+        cur_state: read(format!("{device}/cur_state"))?.parse()?,
+        max_state: read(format!("{device}/max_state"))?.parse()?,
+        device_type: read(format!("{device}/type"))?.clone(),
+        zone_type: read(format!("{zone}/type"))?.clone(),
+        temperature: millicelsius(read(format!("{zone}/temp"))?)?,
+        trip_points,
+    })
+}
+
+impl Row for FanStatus {
+    const HEADERS: &'static [&'static str] = &[
+        "mode",
+        "cur_state",
+        "max_state",
+        "device_type",
+        "zone_type",
+        "temperature",
+        "trip_points",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.mode.clone(),
+            self.cur_state.to_string(),
+            self.max_state.to_string(),
+            self.device_type.clone(),
+            self.zone_type.clone(),
+            self.temperature.to_string(),
+            // `index:kind:temperature` for each trip point.
+            join_cell(
+                self.trip_points
+                    .iter()
+                    .map(|t| format!("{}:{}:{}", t.index, t.kind, t.temperature)),
+            ),
+        ]
+    }
+}
+
+pub async fn print_fan_status(config: &Config, format: OutputFormat) -> anyhow::Result<()> {
+    let status = fan_status(config).await?;
+    output::print_record(format, &status, print_fan_status_text)
+}
+
+/// Reports that the fan `setting` was set to `value`.
+pub fn print_fan_setting(
+    format: OutputFormat,
+    setting: &'static str,
+    value: String,
+) -> anyhow::Result<()> {
+    output::print_record(format, &FanSetting { setting, value }, |record| {
+        println!("Fan {} set to {}", record.setting, record.value)
+    })
+}
+
+fn print_fan_status_text(status: &FanStatus) {
+    println!("Fan mode: {}", status.mode);
+    println!(
+        "Cooling device ({}): state {}/{}",
+        status.device_type, status.cur_state, status.max_state
+    );
+    println!(
+        "Thermal zone ({}): {:.1}°C",
+        status.zone_type, status.temperature
+    );
+    for trip in &status.trip_points {
+        println!(
+            "  Trip point {} [{}]: {:.1}°C",
+            trip.index, trip.kind, trip.temperature
+        );
+    }
+}
+
+fn millicelsius(value: &str) -> anyhow::Result<f64> {
+    Ok(value.parse::<i64>()? as f64 / 1000.0)
 }
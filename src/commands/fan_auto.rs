@@ -0,0 +1,297 @@
+use std::time::{Duration, Instant};
+
+use chrono::NaiveTime;
+
+use crate::{
+    cli::{FanMode, FanSpeed},
+    commands::{fan, thermal},
+    config::{Config, CurvePoint, FanConfig, FanSchedule, Node},
+    ssh,
+};
+
+/// A quiet window with its bounds parsed.
+struct ParsedWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+    max_state: i32,
+}
+
+impl ParsedWindow {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+fn parse_schedule(schedule: &FanSchedule) -> anyhow::Result<Vec<ParsedWindow>> {
+    schedule
+        .windows
+        .iter()
+        .map(|window| {
+            let parse = |value: &str| {
+                NaiveTime::parse_from_str(value, "%H:%M").map_err(|e| {
+                    anyhow::anyhow!("Invalid quiet hours time '{value}', expected HH:MM: {e}")
+                })
+            };
+            Ok(ParsedWindow {
+                start: parse(&window.start)?,
+                end: parse(&window.end)?,
+                max_state: window.max_state,
+            })
+        })
+        .collect()
+}
+
+/// Lowest cap of the quiet windows active at `time`, unless the hottest node is above the
+/// schedule's override temperature.
+fn quiet_cap(
+    windows: &[ParsedWindow],
+    schedule: &FanSchedule,
+    time: NaiveTime,
+    temperature: f64,
+) -> Option<i32> {
+    if temperature >= schedule.override_temperature {
+        return None;
+    }
+    windows
+        .iter()
+        .filter(|window| window.contains(time))
+        .map(|window| window.max_state)
+        .min()
+}
+
+/// Maps the hottest node temperature to a fan state, with hysteresis and a minimum dwell
+/// time on the way down. Speeding up always happens right away.
+struct CurveController {
+    curve: Vec<CurvePoint>,
+    hysteresis: f64,
+    min_dwell: Duration,
+    max_state: i32,
+    current: Option<i32>,
+    last_change: Option<Instant>,
+}
+
+impl CurveController {
+    fn new(fan_config: &FanConfig, max_state: i32) -> Self {
+        CurveController {
+            curve: fan_config.curve.clone(),
+            hysteresis: fan_config.hysteresis,
+            min_dwell: Duration::from_secs(fan_config.min_dwell_secs),
+            max_state,
+            current: None,
+            last_change: None,
+        }
+    }
+
+    fn state_for(&self, temperature: f64) -> i32 {
+        self.curve
+            .iter()
+            .filter(|point| temperature >= point.temperature)
+            .map(|point| point.state)
+            .max()
+            .unwrap_or(0)
+            .clamp(0, self.max_state)
+    }
+
+    /// Returns the state to apply, or `None` when the fan should stay where it is.
+    /// `cap` limits the resulting state, e.g. during quiet hours.
+    fn update(&mut self, temperature: f64, cap: Option<i32>, now: Instant) -> Option<i32> {
+        let cap = cap.unwrap_or(self.max_state);
+        let rising = self.state_for(temperature).min(cap);
+        // Slowing down only happens once the temperature is `hysteresis` below the point.
+        let falling = self.state_for(temperature + self.hysteresis).min(cap);
+        let target = match self.current {
+            Some(current) if rising > current => rising,
+            Some(current) if falling < current => falling,
+            Some(current) => current,
+            None => rising,
+        };
+        if self.current == Some(target) {
+            return None;
+        }
+        let slowing_down = self.current.is_some_and(|current| target < current);
+        if slowing_down
+            && let Some(last_change) = self.last_change
+            && now.duration_since(last_change) < self.min_dwell
+        {
+            log::debug!("Holding fan state {:?} (dwell time)", self.current);
+            return None;
+        }
+        self.force(target, now)
+    }
+
+    /// Applies `state` regardless of the dwell time.
+    fn force(&mut self, state: i32, now: Instant) -> Option<i32> {
+        if self.current == Some(state) {
+            return None;
+        }
+        self.current = Some(state);
+        self.last_change = Some(now);
+        Some(state)
+    }
+}
+
+/// Drives the controller fan from the temperature of every powered node in `nodes` until
+/// Ctrl-C, then hands control back to the LPI3H's built-in mode.
+///
+/// Quiet hours from the fan schedule cap the curve, except while the hottest node is above
+/// the schedule's override temperature.
+pub async fn fan_auto(config: &Config, nodes: &[&Node]) -> anyhow::Result<()> {
+    let max_state = {
+        let session = ssh::connect_controller(config).await?;
+        fan::discover_fan(&session, config).await?.max_state
+    };
+    let windows = parse_schedule(&config.fan.schedule)?;
+    fan::fan_mode(config, &FanMode::Disabled).await?;
+
+    let mut quiet = false;
+    let mut controller = CurveController::new(&config.fan, max_state);
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.fan.interval_secs.max(1)));
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = ticker.tick() => {}
+        }
+
+        let temperatures = thermal::read_cluster_temperatures(config, nodes).await;
+        let now = Instant::now();
+        let target = match temperatures
+            .iter()
+            .max_by(|a, b| a.temperature.total_cmp(&b.temperature))
+        {
+            Some(hottest) => {
+                log::info!(
+                    "Hottest node: slot {} ({}) at {:.1}°C",
+                    hottest.slot_number,
+                    hottest.hostname,
+                    hottest.temperature
+                );
+                let cap = quiet_cap(
+                    &windows,
+                    &config.fan.schedule,
+                    chrono::Local::now().time(),
+                    hottest.temperature,
+                );
+                match (cap, quiet) {
+                    (Some(max_state), false) => {
+                        log::info!("Quiet hours: capping the fan at state {max_state}")
+                    }
+                    (None, true) => log::info!("Quiet hours cap lifted"),
+                    _ => {}
+                }
+                quiet = cap.is_some();
+                controller.update(hottest.temperature, cap, now)
+            }
+            None => {
+                log::warn!("No node temperature could be read, running the fan at full speed");
+                controller.force(max_state, now)
+            }
+        };
+
+        if let Some(state) = target {
+            log::info!("Setting fan speed to {state}");
+            if let Err(e) = fan::fan_speed(config, &FanSpeed(state)).await {
+                log::error!("Failed to set fan speed: {}", e);
+                // Forget the state so the next poll tries again.
+                controller.current = None;
+            }
+        }
+    }
+
+    log::info!("Restoring the controller's automatic fan mode");
+    fan::fan_mode(config, &FanMode::Enabled).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QuietWindow;
+
+    fn controller() -> (CurveController, Instant) {
+        // 45°C → 1, 55°C → 2, 65°C → 3, 75°C → 4, 3°C hysteresis, 60 s dwell.
+        (
+            CurveController::new(&FanConfig::default(), 4),
+            Instant::now(),
+        )
+    }
+
+    #[test]
+    fn rising_applies_the_curve_right_away() {
+        let (mut controller, start) = controller();
+        assert_eq!(controller.update(40.0, None, start), Some(0));
+        assert_eq!(controller.update(56.0, None, start), Some(2));
+        assert_eq!(controller.update(80.0, None, start), Some(4));
+        assert_eq!(controller.update(80.0, None, start), None);
+    }
+
+    #[test]
+    fn falling_within_the_hysteresis_keeps_the_state() {
+        let (mut controller, start) = controller();
+        let later = start + Duration::from_secs(600);
+        controller.update(56.0, None, start);
+        assert_eq!(controller.update(53.0, None, later), None);
+        assert_eq!(controller.update(52.5, None, later), None);
+        assert_eq!(controller.update(51.9, None, later), Some(1));
+    }
+
+    #[test]
+    fn dwell_only_delays_slowing_down() {
+        let (mut controller, start) = controller();
+        controller.update(66.0, None, start);
+        let soon = start + Duration::from_secs(10);
+        assert_eq!(controller.update(40.0, None, soon), None);
+        assert_eq!(controller.update(76.0, None, soon), Some(4));
+        let after_dwell = soon + Duration::from_secs(60);
+        assert_eq!(controller.update(40.0, None, after_dwell), Some(0));
+    }
+
+    #[test]
+    fn cap_limits_the_state() {
+        let (mut controller, start) = controller();
+        assert_eq!(controller.update(80.0, Some(1), start), Some(1));
+        let later = start + Duration::from_secs(600);
+        assert_eq!(controller.update(80.0, None, later), Some(4));
+        assert_eq!(controller.update(80.0, Some(2), later), None);
+        let after_dwell = later + Duration::from_secs(60);
+        assert_eq!(controller.update(80.0, Some(2), after_dwell), Some(2));
+    }
+
+    fn at(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    fn night() -> (Vec<ParsedWindow>, FanSchedule) {
+        let schedule = FanSchedule {
+            override_temperature: 70.0,
+            windows: vec![QuietWindow {
+                start: "22:00".to_owned(),
+                end: "07:00".to_owned(),
+                max_state: 1,
+            }],
+        };
+        (parse_schedule(&schedule).unwrap(), schedule)
+    }
+
+    #[test]
+    fn windows_wrap_around_midnight() {
+        let (windows, _) = night();
+        assert!(windows[0].contains(at("23:00")));
+        assert!(windows[0].contains(at("06:59")));
+        assert!(!windows[0].contains(at("07:00")));
+        assert!(!windows[0].contains(at("12:00")));
+    }
+
+    #[test]
+    fn quiet_cap_is_lifted_at_the_override_temperature() {
+        let (windows, schedule) = night();
+        assert_eq!(quiet_cap(&windows, &schedule, at("23:00"), 69.9), Some(1));
+        assert_eq!(quiet_cap(&windows, &schedule, at("23:00"), 70.0), None);
+        assert_eq!(quiet_cap(&windows, &schedule, at("12:00"), 50.0), None);
+    }
+}
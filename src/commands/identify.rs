@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use futures::future::join_all;
+
+use crate::{
+    commands::power,
+    config::{Config, Model, Node},
+    output::{NodeResult, OperationReport, Outcome},
+    ssh,
+};
+
+const LEDS_CLASS: &str = "/sys/class/leds";
+
+/// LEDs to blink, by order of preference. Raspberry Pi kernels before 6.1 named the
+/// activity and power LEDs `led0` and `led1`.
+fn led_candidates(model: &Model) -> &'static [&'static str] {
+    match model {
+        Model::CM4 => &["ACT", "led0", "PWR", "led1"],
+        Model::CM5 => &["ACT", "PWR"],
+        Model::LPI3H => &["sys-led", "blue:status", "green:status", "status"],
+    }
+}
+
+/// Blinks an LED of each selected node that is on for `duration`, or until Ctrl-C, then
+/// puts the LED's original trigger back.
+pub async fn identify(config: &Config, nodes: &[&Node], duration: Duration) -> OperationReport {
+    let results = join_all(nodes.iter().map(|&node| async move {
+        if !power::power_status(config, &node.slot_number).await {
+            return NodeResult::new(node, Outcome::Skipped, "powered off");
+        }
+        match blink(config, node, duration).await {
+            Ok(led) => NodeResult::new(node, Outcome::Ok, format!("blinked {led}")),
+            Err(e) => NodeResult::new(node, Outcome::Failed, e.to_string()),
+        }
+    }))
+    .await;
+    OperationReport {
+        action: "identify".to_owned(),
+        results,
+    }
+}
+
+async fn blink(config: &Config, node: &Node, duration: Duration) -> anyhow::Result<String> {
+    let session = ssh::connect(config, node).await?;
+    let leds = ssh::run(&session, &format!("ls {LEDS_CLASS}")).await?;
+    let leds: Vec<&str> = leds.split_whitespace().collect();
+    let led = led_candidates(&node.model)
+        .iter()
+        .find(|candidate| leds.contains(candidate))
+        .copied()
+        .or_else(|| {
+            (node.model == Model::LPI3H)
+                .then(|| leds.first().copied())
+                .flatten()
+        })
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No known LED found in {LEDS_CLASS}, available: {}",
+                leds.join(", ")
+            )
+        })?;
+    let dir = format!("{LEDS_CLASS}/{led}");
+
+    // The active trigger is the one in brackets, e.g. `none [mmc0] timer`.
+    let triggers = ssh::run(&session, &format!("cat {dir}/trigger")).await?;
+    let trigger = triggers
+        .split_whitespace()
+        .find_map(|t| t.strip_prefix('[')?.strip_suffix(']'))
+        .unwrap_or("none")
+        .to_owned();
+    let brightness = ssh::run(&session, &format!("cat {dir}/brightness")).await?;
+    log::info!(
+        "Blinking {led} on {} (trigger was {trigger})",
+        node.hostname
+    );
+
+    ssh::run(
+        &session,
+        &format!(
+            "echo timer | sudo tee {dir}/trigger >/dev/null && \
+             echo 100 | sudo tee {dir}/delay_on {dir}/delay_off >/dev/null"
+        ),
+    )
+    .await?;
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    // Writing the brightness would detach any other trigger, so only do it for `none`.
+    let mut restore = format!("echo {trigger} | sudo tee {dir}/trigger >/dev/null");
+    if trigger == "none" {
+        restore += &format!(
+            " && echo {} | sudo tee {dir}/brightness >/dev/null",
+            brightness.trim()
+        );
+    }
+    ssh::run(&session, &restore).await?;
+    Ok(led.to_owned())
+}
@@ -0,0 +1,331 @@
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::{DateTime, Local};
+use futures::future::join_all;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    commands::{power, telemetry},
+    config::{Config, Model, Node},
+    output::{self, OutputFormat, Row, join_cell},
+    ssh,
+};
+
+/// Hardware facts of one node, as stored in the inventory file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeInventory {
+    pub slot_number: i32,
+    pub hostname: String,
+    pub collected_at: DateTime<Local>,
+    /// Content of `/proc/device-tree/model`.
+    pub device_tree_model: Option<String>,
+    pub detected_model: Option<Model>,
+    pub serial_number: Option<String>,
+    /// Interface name to MAC address.
+    pub mac_addresses: BTreeMap<String, String>,
+    pub memory_total_mib: Option<u64>,
+    pub storage: Vec<StorageDevice>,
+    /// `vcgencmd bootloader_version`, only read on CM4 and CM5.
+    pub bootloader: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageDevice {
+    pub name: String,
+    pub size_bytes: u64,
+    pub model: Option<String>,
+}
+
+/// Inventory file content: context name, then slot number.
+type InventoryFile = BTreeMap<String, BTreeMap<i32, NodeInventory>>;
+
+/// What `inventory` found, compared with the previous run.
+#[derive(Debug, Serialize)]
+pub struct InventoryReport {
+    pub nodes: Vec<NodeInventory>,
+    /// Selected nodes that could not be reached; their previous facts are kept.
+    pub unreachable: Vec<i32>,
+    pub changes: Vec<Change>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub slot_number: i32,
+    pub hostname: String,
+    pub field: String,
+    pub previous: Option<serde_json::Value>,
+    pub current: Option<serde_json::Value>,
+}
+
+/// A node whose detected module is not the `model` configured for its slot.
+#[derive(Debug, Serialize)]
+pub struct Mismatch {
+    pub slot_number: i32,
+    pub hostname: String,
+    pub configured: Model,
+    pub detected: Option<Model>,
+    pub device_tree_model: Option<String>,
+}
+
+impl Row for NodeInventory {
+    const HEADERS: &'static [&'static str] = &[
+        "slot_number",
+        "hostname",
+        "detected_model",
+        "serial_number",
+        "mac_addresses",
+        "memory_total_mib",
+        "storage",
+        "bootloader",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.slot_number.to_string(),
+            self.hostname.clone(),
+            self.detected_model
+                .map(|m| format!("{m:?}"))
+                .unwrap_or_default(),
+            self.serial_number.clone().unwrap_or_default(),
+            join_cell(
+                self.mac_addresses
+                    .iter()
+                    .map(|(name, mac)| format!("{name}={mac}")),
+            ),
+            self.memory_total_mib
+                .map(|m| m.to_string())
+                .unwrap_or_default(),
+            join_cell(
+                self.storage
+                    .iter()
+                    .map(|d| format!("{}:{}", d.name, d.size_bytes)),
+            ),
+            self.bootloader.clone().unwrap_or_default(),
+        ]
+    }
+
+    fn table_cells(&self) -> Vec<String> {
+        let mut cells = self.cells();
+        cells[6] = join_cell(
+            self.storage
+                .iter()
+                .map(|d| format!("{} {:.1}G", d.name, d.size_bytes as f64 / 1e9)),
+        );
+        cells
+    }
+}
+
+/// Collects the hardware facts of the selected nodes that are on, reports what changed
+/// since the facts stored for `context`, and stores the new ones.
+pub async fn inventory(
+    config: &Config,
+    context: &str,
+    nodes: &[&Node],
+    inventory_file: &Path,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut file: InventoryFile = match std::fs::read_to_string(inventory_file) {
+        Ok(content) => serde_json::from_str(&content)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => InventoryFile::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let stored = file.entry(context.to_owned()).or_default();
+
+    let collected = join_all(nodes.iter().map(|&node| async move {
+        if !power::power_status(config, &node.slot_number).await {
+            return (node, Err(anyhow::anyhow!("node is off")));
+        }
+        (node, collect(config, node).await)
+    }))
+    .await;
+
+    let mut report = InventoryReport {
+        nodes: vec![],
+        unreachable: vec![],
+        changes: vec![],
+        mismatches: vec![],
+    };
+    for (node, facts) in collected {
+        let facts = match facts {
+            Ok(facts) => facts,
+            Err(e) => {
+                log::warn!(
+                    "Cannot collect the inventory of node {}: {}",
+                    node.slot_number,
+                    e
+                );
+                report.unreachable.push(node.slot_number);
+                continue;
+            }
+        };
+        if let Some(previous) = stored.get(&node.slot_number) {
+            report.changes.extend(diff(previous, &facts));
+        }
+        if facts.detected_model != Some(node.model) {
+            report.mismatches.push(Mismatch {
+                slot_number: node.slot_number,
+                hostname: node.hostname.clone(),
+                configured: node.model,
+                detected: facts.detected_model,
+                device_tree_model: facts.device_tree_model.clone(),
+            });
+        }
+        stored.insert(node.slot_number, facts.clone());
+        report.nodes.push(facts);
+    }
+
+    if let Some(parent) = inventory_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(inventory_file, serde_json::to_string_pretty(&file)?)?;
+
+    match format {
+        OutputFormat::Table => print_report(&report, inventory_file),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(&report)?),
+        OutputFormat::Csv => output::print_rows(format, &report.nodes)?,
+    }
+    Ok(())
+}
+
+fn print_report(report: &InventoryReport, inventory_file: &Path) {
+    if !report.nodes.is_empty() {
+        // Printing rows as a table cannot fail.
+        let _ = output::print_rows(OutputFormat::Table, &report.nodes);
+    }
+    for slot in &report.unreachable {
+        println!("Slot {slot}: not reachable, previous facts kept");
+    }
+    if !report.changes.is_empty() {
+        println!("\nChanges since the previous run:");
+        for change in &report.changes {
+            let show = |value: &Option<serde_json::Value>| {
+                value
+                    .as_ref()
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "-".to_owned())
+            };
+            println!(
+                "  Slot {} ({}): {} {} -> {}",
+                change.slot_number,
+                change.hostname,
+                change.field,
+                show(&change.previous),
+                show(&change.current)
+            );
+        }
+    }
+    for mismatch in &report.mismatches {
+        println!(
+            "WARNING: slot {} ({}) is configured as {:?} but reports {}",
+            mismatch.slot_number,
+            mismatch.hostname,
+            mismatch.configured,
+            mismatch
+                .device_tree_model
+                .as_deref()
+                .unwrap_or("no device-tree model")
+        );
+    }
+    println!("Inventory saved to {}", inventory_file.display());
+}
+
+/// Fields that differ between two runs, compared on their JSON form.
+fn diff(previous: &NodeInventory, current: &NodeInventory) -> Vec<Change> {
+    let (
+        Ok(serde_json::Value::Object(previous_fields)),
+        Ok(serde_json::Value::Object(current_fields)),
+    ) = (
+        serde_json::to_value(previous),
+        serde_json::to_value(current),
+    )
+    else {
+        return vec![];
+    };
+    let mut fields: Vec<&String> = previous_fields
+        .keys()
+        .chain(current_fields.keys())
+        .collect();
+    fields.sort();
+    fields.dedup();
+    fields
+        .into_iter()
+        .filter(|field| *field != "collected_at")
+        .filter(|field| previous_fields.get(*field) != current_fields.get(*field))
+        .map(|field| Change {
+            slot_number: current.slot_number,
+            hostname: current.hostname.clone(),
+            field: field.clone(),
+            previous: previous_fields.get(field).cloned(),
+            current: current_fields.get(field).cloned(),
+        })
+        .collect()
+}
+
+/// Reads every fact in one SSH round trip; the output is split in `@section` blocks.
+async fn collect(config: &Config, node: &Node) -> anyhow::Result<NodeInventory> {
+    let mut script = String::from(
+        "echo @model; tr -d '\\0' 2>/dev/null < /proc/device-tree/model; echo; \
+         echo @serial; tr -d '\\0' 2>/dev/null < /proc/device-tree/serial-number \
+           || awk '/^Serial/ {print $3}' /proc/cpuinfo; echo; \
+         echo @macs; for i in /sys/class/net/*; do n=${i##*/}; [ \"$n\" = lo ] && continue; \
+           echo \"$n $(cat $i/address)\"; done; \
+         echo @meminfo; grep '^MemTotal:' /proc/meminfo; \
+         echo @storage; lsblk -d -b -n -o NAME,SIZE,MODEL 2>/dev/null; ",
+    );
+    if matches!(node.model, Model::CM4 | Model::CM5) {
+        script.push_str("echo @bootloader; vcgencmd bootloader_version 2>/dev/null; ");
+    }
+    script.push_str("true");
+
+    let session = ssh::connect(config, node).await?;
+    let output = session.command("sh").arg("-c").arg(script).output().await?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let sections = telemetry::sections(&stdout);
+    let section = |name: &str| sections.get(name).map(Vec::as_slice).unwrap_or_default();
+    let first = |name: &str| section(name).first().map(|l| l.to_string());
+
+    let device_tree_model = first("model");
+    Ok(NodeInventory {
+        slot_number: node.slot_number,
+        hostname: node.hostname.clone(),
+        collected_at: Local::now(),
+        detected_model: device_tree_model
+            .as_deref()
+            .and_then(Model::from_device_tree),
+        device_tree_model,
+        serial_number: first("serial"),
+        mac_addresses: section("macs")
+            .iter()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(name, mac)| (name.to_owned(), mac.to_owned()))
+            .collect(),
+        memory_total_mib: telemetry::meminfo_mib(section("meminfo"), "MemTotal:"),
+        storage: section("storage")
+            .iter()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let name = fields.next()?.to_owned();
+                let size_bytes = fields.next()?.parse().ok()?;
+                let model = fields.collect::<Vec<_>>().join(" ");
+                Some(StorageDevice {
+                    name,
+                    size_bytes,
+                    model: Some(model).filter(|m| !m.is_empty()),
+                })
+            })
+            .collect(),
+        // First line is the build date, the `version` line holds the commit.
+        bootloader: {
+            let lines = section("bootloader");
+            let version = lines.iter().find_map(|l| l.strip_prefix("version "));
+            match (lines.first(), version) {
+                (Some(date), Some(version)) => Some(format!("{date} ({version})")),
+                (Some(date), None) => Some(date.to_string()),
+                _ => None,
+            }
+        },
+    })
+}
@@ -1,2 +1,17 @@
+pub mod calibrate;
+pub mod collect;
+pub mod config;
+pub mod dashboard;
+pub mod discover;
+pub mod exec;
+pub mod fan;
+pub mod fan_auto;
+pub mod identify;
+pub mod inventory;
 pub mod power;
-pub mod fan;
\ No newline at end of file
+pub mod protect;
+pub mod telemetry;
+pub mod thermal;
+pub mod transfer;
+pub mod trust;
+pub mod watch;
@@ -1,319 +1,360 @@
-use std::thread::sleep;
+use std::{collections::BTreeMap, time::Duration};
 
 use anyhow::{self};
+use futures::future::join_all;
 use log;
-use openssh::{KnownHosts, Session};
-
-use crate::Config;
-
-pub async fn print_cluster_power_status(config: &Config) {
-    for node in &config.cluster.nodes {
-        let status = power_status(config, &node.slot_number).await;
-        let state_str = if status { "ON" } else { "OFF" };
-        println!(
-            "Slot {} ({}) [{}]: {}",
-            node.slot_number,
-            node.hostname,
-            format!("{:?}", node.model),
-            state_str
-        );
+use serde_derive::Serialize;
+use tokio::time::sleep;
+
+use crate::{
+    commands::telemetry::{self, NodeDetails},
+    config::{Config, Model, Node},
+    output::{self, NodeResult, OperationReport, Outcome, OutputFormat, Row, join_cell},
+    ssh,
+};
+
+/// Power state of one node, as printed by `power status`.
+#[derive(Debug, Serialize)]
+pub struct NodeStatus {
+    pub slot_number: i32,
+    pub hostname: String,
+    pub model: Model,
+    pub power: PowerState,
+    pub groups: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+    /// Only read with `--details`, for nodes that are on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<NodeDetails>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerState {
+    On,
+    Off,
+}
+
+impl Row for NodeStatus {
+    const HEADERS: &'static [&'static str] = &[
+        "slot_number",
+        "hostname",
+        "model",
+        "power",
+        "groups",
+        "labels",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.slot_number.to_string(),
+            self.hostname.clone(),
+            format!("{:?}", self.model),
+            format!("{:?}", self.power).to_lowercase(),
+            join_cell(&self.groups),
+            join_cell(self.labels.iter().map(|(k, v)| format!("{k}={v}"))),
+        ]
     }
 }
 
-pub async fn print_node_power_status(config: &Config, slot_number: i32) {
-    if let Some(node) = config
-        .cluster
-        .nodes
-        .iter()
-        .find(|n| n.slot_number == slot_number)
-    {
-        let status = power_status(config, &slot_number).await;
-        let state_str = if status { "ON" } else { "OFF" };
-        println!(
-            "Slot {} ({}) [{}]: {}",
-            node.slot_number,
-            node.hostname,
-            format!("{:?}", node.model),
-            state_str
-        );
+pub async fn node_status(config: &Config, node: &Node) -> NodeStatus {
+    let power = if power_status(config, &node.slot_number).await {
+        PowerState::On
     } else {
-        log::error!("Node with slot number {} not found", slot_number);
+        PowerState::Off
+    };
+    NodeStatus {
+        slot_number: node.slot_number,
+        hostname: node.hostname.clone(),
+        model: node.model,
+        power,
+        groups: config
+            .groups_of(node)
+            .into_iter()
+            .map(str::to_owned)
+            .collect(),
+        labels: node.labels.clone(),
+        details: None,
     }
 }
 
-pub async fn boot_all_nodes(config: &Config) {
-    for node in &config.cluster.nodes {
-        if node.slot_number == 1 {
-            continue; // Skip controller node
-        }
-        let status = power_status(config, &node.slot_number).await;
-        if status {
-            log::info!("Node {} is already on, skipping.", node.slot_number);
-            continue;
-        }
-        match node.model {
-            crate::Model::CM5 => {
-                if let Err(e) = boot_cm5_single_node(
-                    &config
-                        .cluster
-                        .nodes
-                        .iter()
-                        .find(|n| n.slot_number == 1)
-                        .unwrap()
-                        .hostname,
-                    &config.ssh_username,
-                    &node.slot_number,
-                )
-                .await
-                {
-                    log::error!("Failed to boot CM5 node {}: {}", node.slot_number, e);
-                }
+/// A `NodeStatus` printed with its details as extra columns.
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct DetailedStatus(pub NodeStatus);
+
+impl Row for DetailedStatus {
+    const HEADERS: &'static [&'static str] = &[
+        "slot_number",
+        "hostname",
+        "model",
+        "power",
+        "groups",
+        "labels",
+        "uptime_secs",
+        "load",
+        "memory_total_mib",
+        "memory_available_mib",
+        "temperature",
+        "throttled",
+        "kernel",
+        "os_release",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        let mut cells = self.0.cells();
+        let Some(details) = &self.0.details else {
+            cells.resize(Self::HEADERS.len(), String::new());
+            return cells;
+        };
+        let text = |value: Option<String>| value.unwrap_or_default();
+        cells.extend([
+            text(details.uptime_secs.map(|s| s.to_string())),
+            text(
+                details
+                    .load
+                    .map(|load| join_cell(load.map(|l| l.to_string()))),
+            ),
+            text(details.memory_total_mib.map(|m| m.to_string())),
+            text(details.memory_available_mib.map(|m| m.to_string())),
+            text(details.temperature.map(|t| t.to_string())),
+            text(details.throttled.as_ref().map(|t| t.raw.clone())),
+            text(details.kernel.clone()),
+            text(details.os_release.clone()),
+        ]);
+        cells
+    }
+
+    fn table_cells(&self) -> Vec<String> {
+        let mut cells = self.cells();
+        if let Some(details) = &self.0.details {
+            if let Some(secs) = details.uptime_secs {
+                cells[6] = format!(
+                    "{}d {}h {}m",
+                    secs / 86400,
+                    secs / 3600 % 24,
+                    secs / 60 % 60
+                );
             }
-            crate::Model::CM4 => {
-                if let Err(e) = boot_cm4_single_node(
-                    &config
-                        .cluster
-                        .nodes
-                        .iter()
-                        .find(|n| n.slot_number == 1)
-                        .unwrap()
-                        .hostname,
-                    &config.ssh_username,
-                    &node.slot_number,
-                )
-                .await
-                {
-                    log::error!("Failed to boot CM4 node {}: {}", node.slot_number, e);
-                }
+            if let Some([one, five, fifteen]) = details.load {
+                cells[7] = format!("{one:.2} {five:.2} {fifteen:.2}");
+            }
+            if let Some(temperature) = details.temperature {
+                cells[10] = format!("{temperature:.1}°C");
             }
-            crate::Model::LPI3H => {
-                log::error!("Boot for LPI3H node {} not implemented!", node.slot_number);
+            if let Some(throttled) = &details.throttled {
+                cells[11] = match throttled.flags.as_slice() {
+                    [] => "no".to_owned(),
+                    flags => flags.join(","),
+                };
             }
         }
+        cells
+    }
+}
+
+pub async fn print_power_status(
+    config: &Config,
+    nodes: &[&Node],
+    format: OutputFormat,
+    details: bool,
+) -> anyhow::Result<()> {
+    if !details {
+        let statuses = join_all(nodes.iter().map(|node| node_status(config, node))).await;
+        return output::print_rows(format, &statuses);
     }
+    let statuses = join_all(nodes.iter().map(|&node| async move {
+        let mut status = node_status(config, node).await;
+        if status.power == PowerState::On {
+            match telemetry::read_details(config, node).await {
+                Ok(details) => status.details = Some(details),
+                Err(e) => log::warn!("Failed to read details of node {}: {}", node.slot_number, e),
+            }
+        }
+        DetailedStatus(status)
+    }))
+    .await;
+    output::print_rows(format, &statuses)
 }
 
-pub async fn boot_single_node(config: &Config, slot_number: i32) {
-    if slot_number == 1 {
-        log::error!("Cannot turn on the controller node !");
-        return;
+/// Boots the selected nodes that are off. The controller is never touched.
+pub async fn boot_nodes(config: &Config, nodes: &[&Node]) -> OperationReport {
+    let mut results = Vec::new();
+    for node in nodes {
+        results.push(boot_node(config, node).await);
+    }
+    OperationReport {
+        action: "power on".to_owned(),
+        results,
     }
+}
 
-    let node_power_status = power_status(&config, &7).await;
+/// Shuts down the selected nodes that are on. The controller is never touched.
+pub async fn shutdown_nodes(config: &Config, nodes: &[&Node]) -> OperationReport {
+    let mut results = Vec::new();
+    for node in nodes {
+        results.push(shutdown_node(config, node).await);
+    }
+    OperationReport {
+        action: "power off".to_owned(),
+        results,
+    }
+}
 
-    if node_power_status {
-        log::error!("Node is already on");
-        return;
+/// Shuts down each selected node, waits until it stops answering, then boots it again.
+/// Nodes that are already off are only booted. The controller is never touched.
+pub async fn cycle_nodes(config: &Config, nodes: &[&Node]) -> OperationReport {
+    let mut results = Vec::new();
+    for node in nodes {
+        results.push(cycle_node(config, node).await);
+    }
+    OperationReport {
+        action: "power cycle".to_owned(),
+        results,
     }
+}
 
-    let cluster_controller_hostname = &config
-        .cluster
-        .nodes
-        .iter()
-        .find(|n| n.slot_number == 1)
-        .unwrap()
-        .hostname;
-    let model = &config
-        .cluster
-        .nodes
-        .iter()
-        .find(|n| n.slot_number == slot_number)
-        .unwrap()
-        .model;
-    match model {
-        crate::Model::CM5 => {
-            if let Err(e) = boot_cm5_single_node(
-                cluster_controller_hostname,
-                &config.ssh_username,
-                &slot_number,
-            )
-            .await
-            {
-                log::error!("Failed to shutdown CM5 node: {}", e);
-            }
-        }
-        crate::Model::CM4 => {
-            if let Err(e) = boot_cm4_single_node(
-                cluster_controller_hostname,
-                &config.ssh_username,
-                &slot_number,
-            )
-            .await
-            {
-                log::error!("Failed to shutdown CM4 node: {}", e);
-            }
+pub async fn boot_node(config: &Config, node: &Node) -> NodeResult {
+    if node.slot_number == 1 {
+        return NodeResult::new(node, Outcome::Skipped, "controller node");
+    }
+    if power_status(config, &node.slot_number).await {
+        log::info!("Node {} is already on, skipping.", node.slot_number);
+        return NodeResult::new(node, Outcome::Skipped, "already on");
+    }
+    let result = match node.model {
+        Model::CM5 => boot_cm5_single_node(config, node).await,
+        Model::CM4 => boot_cm4_single_node(config, node).await,
+        Model::LPI3H => Err(anyhow::anyhow!("boot is not implemented for LPI3H")),
+    };
+    match result {
+        Ok(()) => NodeResult::new(node, Outcome::Ok, "booted"),
+        Err(e) => {
+            log::error!("Failed to boot node {}: {}", node.slot_number, e);
+            NodeResult::new(node, Outcome::Failed, e.to_string())
         }
-        crate::Model::LPI3H => log::error!("No implemented yet !"),
     }
 }
 
-pub async fn shutdown_all_nodes(config: &Config) {
-    for node in &config.cluster.nodes {
-        if node.slot_number == 1 {
-            continue; // Skip controller node
-        }
-        let status = power_status(config, &node.slot_number).await;
-        if !status {
-            log::info!("Node {} is already off, skipping.", node.slot_number);
-            continue;
-        }
-        match node.model {
-            crate::Model::CM5 => {
-                if let Err(e) = shutdown_cm5_single_node(
-                    &config
-                        .cluster
-                        .nodes
-                        .iter()
-                        .find(|n| n.slot_number == 1)
-                        .unwrap()
-                        .hostname,
-                    &config.ssh_username,
-                    &node.hostname,
-                    &node.slot_number,
-                )
-                .await
-                {
-                    log::error!("Failed to shutdown CM5 node {}: {}", node.slot_number, e);
-                }
-            }
-            crate::Model::CM4 => {
-                if let Err(e) = shutdown_cm4_single_node(
-                    &config
-                        .cluster
-                        .nodes
-                        .iter()
-                        .find(|n| n.slot_number == 1)
-                        .unwrap()
-                        .hostname,
-                    &config.ssh_username,
-                    &node.hostname,
-                    &node.slot_number,
-                )
-                .await
-                {
-                    log::error!("Failed to shutdown CM4 node {}: {}", node.slot_number, e);
-                }
-            }
-            crate::Model::LPI3H => {
-                log::error!(
-                    "Shutdown for LPI3H node {} not implemented!",
-                    node.slot_number
-                );
-            }
+pub async fn shutdown_node(config: &Config, node: &Node) -> NodeResult {
+    if node.slot_number == 1 {
+        return NodeResult::new(node, Outcome::Skipped, "controller node");
+    }
+    if !power_status(config, &node.slot_number).await {
+        log::info!("Node {} is already off, skipping.", node.slot_number);
+        return NodeResult::new(node, Outcome::Skipped, "already off");
+    }
+    let result = match node.model {
+        Model::CM5 => shutdown_cm5_single_node(config, node).await,
+        Model::CM4 => shutdown_cm4_single_node(config, node).await,
+        Model::LPI3H => Err(anyhow::anyhow!("shutdown is not implemented for LPI3H")),
+    };
+    match result {
+        Ok(()) => NodeResult::new(node, Outcome::Ok, "shut down"),
+        Err(e) => {
+            log::error!("Failed to shutdown node {}: {}", node.slot_number, e);
+            NodeResult::new(node, Outcome::Failed, e.to_string())
         }
     }
 }
 
-pub async fn shutdown_single_node(config: &Config, slot_number: i32) {
-    if slot_number == 1 {
-        log::error!("Cannot turn off the controller node !");
-        return;
+/// Reboots the OS of the selected nodes that are on, without touching their power line.
+pub async fn reboot_nodes(config: &Config, nodes: &[&Node]) -> OperationReport {
+    let mut results = Vec::new();
+    for node in nodes {
+        results.push(reboot_node(config, node).await);
     }
+    OperationReport {
+        action: "power reboot".to_owned(),
+        results,
+    }
+}
 
-    let node_power_status = power_status(&config, &7).await;
-
-    if !node_power_status {
-        log::error!("Node is already off");
-        return;
+pub async fn reboot_node(config: &Config, node: &Node) -> NodeResult {
+    if node.slot_number == 1 {
+        return NodeResult::new(node, Outcome::Skipped, "controller node");
     }
+    if !power_status(config, &node.slot_number).await {
+        return NodeResult::new(node, Outcome::Skipped, "off");
+    }
+    match send_ssh_reboot_command(config, node).await {
+        Ok(()) => NodeResult::new(node, Outcome::Ok, "rebooting"),
+        Err(e) => {
+            log::error!("Failed to reboot node {}: {}", node.slot_number, e);
+            NodeResult::new(node, Outcome::Failed, e.to_string())
+        }
+    }
+}
 
-    let cluster_controller_hostname = &config
-        .cluster
-        .nodes
-        .iter()
-        .find(|n| n.slot_number == 1)
-        .unwrap()
-        .hostname;
-    let node_hostname = &config
-        .cluster
-        .nodes
-        .iter()
-        .find(|n| n.slot_number == slot_number)
-        .unwrap()
-        .hostname;
-    let model = &config
-        .cluster
-        .nodes
-        .iter()
-        .find(|n| n.slot_number == slot_number)
-        .unwrap()
-        .model;
-    match model {
-        crate::Model::CM5 => {
-            if let Err(e) = shutdown_cm5_single_node(
-                cluster_controller_hostname,
-                &config.ssh_username,
-                node_hostname,
-                &slot_number,
-            )
-            .await
-            {
-                log::error!("Failed to shutdown CM5 node: {}", e);
-            }
+pub async fn cycle_node(config: &Config, node: &Node) -> NodeResult {
+    if node.slot_number == 1 {
+        return NodeResult::new(node, Outcome::Skipped, "controller node");
+    }
+    if power_status(config, &node.slot_number).await {
+        let shutdown = shutdown_node(config, node).await;
+        if shutdown.outcome == Outcome::Failed {
+            return shutdown;
         }
-        crate::Model::CM4 => {
-            if let Err(e) = shutdown_cm4_single_node(
-                cluster_controller_hostname,
-                &config.ssh_username,
-                node_hostname,
-                &slot_number,
-            )
-            .await
-            {
-                log::error!("Failed to shutdown CM4 node: {}", e);
-            }
+        if !wait_for_power_off(config, node).await {
+            return NodeResult::new(
+                node,
+                Outcome::Failed,
+                format!(
+                    "still answers {}s after shutdown, not booting it again",
+                    CYCLE_OFF_TIMEOUT.as_secs()
+                ),
+            );
         }
-        crate::Model::LPI3H => log::error!("No implemented yet !"),
+        // Give the module time to actually cut power after the OS halted.
+        sleep(Duration::from_secs(2)).await;
+    }
+    let boot = boot_node(config, node).await;
+    match boot.outcome {
+        Outcome::Ok => NodeResult::new(node, Outcome::Ok, "power cycled"),
+        _ => boot,
     }
 }
 
-async fn shutdown_cm4_single_node(
-    controller_hostname: &String,
-    username: &String,
-    hostname: &String,
-    slot_number: &i32,
-) -> anyhow::Result<()> {
-    send_ssh_shutdown_command(&username, &hostname).await?;
-    sleep(std::time::Duration::from_millis(2000));
-    cm4_power_off_button(&username, &controller_hostname, &slot_number).await?;
+/// How long `cycle_nodes` waits for a node to stop answering after shutting it down.
+const CYCLE_OFF_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Waits up to `CYCLE_OFF_TIMEOUT` for `node` to stop answering; false if it never did.
+pub async fn wait_for_power_off(config: &Config, node: &Node) -> bool {
+    let deadline = tokio::time::Instant::now() + CYCLE_OFF_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        if !power_status(config, &node.slot_number).await {
+            return true;
+        }
+        sleep(Duration::from_secs(2)).await;
+    }
+    false
+}
+
+async fn shutdown_cm4_single_node(config: &Config, node: &Node) -> anyhow::Result<()> {
+    send_ssh_shutdown_command(config, node).await?;
+    sleep(Duration::from_millis(2000)).await;
+    cm4_power_off_button(config, &node.slot_number).await?;
     Ok(())
 }
 
-async fn shutdown_cm5_single_node(
-    controller_hostname: &String,
-    username: &String,
-    hostname: &String,
-    slot_number: &i32,
-) -> anyhow::Result<()> {
-    send_ssh_shutdown_command(&username, &hostname).await?;
-    sleep(std::time::Duration::from_millis(2000));
-    cm5_short_push_power_button(&username, &controller_hostname, &slot_number).await?;
+async fn shutdown_cm5_single_node(config: &Config, node: &Node) -> anyhow::Result<()> {
+    send_ssh_shutdown_command(config, node).await?;
+    sleep(Duration::from_millis(2000)).await;
+    cm5_short_push_power_button(config, &node.slot_number).await?;
     Ok(())
 }
 
-async fn boot_cm4_single_node(
-    controller_hostname: &String,
-    username: &String,
-    slot_number: &i32,
-) -> anyhow::Result<()> {
-    cm4_power_on_button(&username, &controller_hostname, &slot_number).await?;
+async fn boot_cm4_single_node(config: &Config, node: &Node) -> anyhow::Result<()> {
+    cm4_power_on_button(config, &node.slot_number).await?;
     Ok(())
 }
 
-async fn boot_cm5_single_node(
-    controller_hostname: &String,
-    username: &String,
-    slot_number: &i32,
-) -> anyhow::Result<()> {
-    cm5_short_push_power_button(&username, &controller_hostname, &slot_number).await?;
+async fn boot_cm5_single_node(config: &Config, node: &Node) -> anyhow::Result<()> {
+    cm5_short_push_power_button(config, &node.slot_number).await?;
     Ok(())
 }
 
-async fn send_ssh_shutdown_command(
-    username: &String,
-    hostname: &String,
-) -> Result<(), anyhow::Error> {
-    let session = Session::connect(format!("{username}@{hostname}"), KnownHosts::Add).await?;
+pub async fn send_ssh_shutdown_command(config: &Config, node: &Node) -> Result<(), anyhow::Error> {
+    let session = ssh::connect(config, node).await?;
     let output = session
         .command("sudo")
         .arg("shutdown")
@@ -325,13 +366,21 @@ async fn send_ssh_shutdown_command(
     Ok(())
 }
 
-async fn cm4_power_off_button(
-    username: &String,
-    controller_hostname: &String,
-    slot_number: &i32,
-) -> anyhow::Result<()> {
-    let session =
-        Session::connect(format!("{username}@{controller_hostname}"), KnownHosts::Add).await?;
+async fn send_ssh_reboot_command(config: &Config, node: &Node) -> anyhow::Result<()> {
+    let session = ssh::connect(config, node).await?;
+    let output = session
+        .command("sudo")
+        .arg("shutdown")
+        .arg("-r")
+        .arg("now")
+        .output()
+        .await?;
+    log::info!("{}", String::from_utf8_lossy(&output.stdout));
+    Ok(())
+}
+
+pub async fn cm4_power_off_button(config: &Config, slot_number: &i32) -> anyhow::Result<()> {
+    let session = ssh::connect_controller(config).await?;
     let output = session
         .command("sudo")
         .arg("gpioset")
@@ -344,13 +393,8 @@ async fn cm4_power_off_button(
     Ok(())
 }
 
-async fn cm4_power_on_button(
-    username: &String,
-    controller_hostname: &String,
-    slot_number: &i32,
-) -> anyhow::Result<()> {
-    let session =
-        Session::connect(format!("{username}@{controller_hostname}"), KnownHosts::Add).await?;
+async fn cm4_power_on_button(config: &Config, slot_number: &i32) -> anyhow::Result<()> {
+    let session = ssh::connect_controller(config).await?;
     let output = session
         .command("sudo")
         .arg("gpioset")
@@ -363,13 +407,10 @@ async fn cm4_power_on_button(
     Ok(())
 }
 
-async fn cm5_short_push_power_button(
-    username: &String,
-    controller_hostname: &String,
-    slot_number: &i32,
-) -> anyhow::Result<()> {
-    let session =
-        Session::connect(format!("{username}@{controller_hostname}"), KnownHosts::Add).await?;
+/// Pulls the slot's power line low for a second: a short press of a CM5's power button,
+/// and a power-on of a CM4 that was off since the line ends high.
+pub async fn cm5_short_push_power_button(config: &Config, slot_number: &i32) -> anyhow::Result<()> {
+    let session = ssh::connect_controller(config).await?;
     // Set GPIO low
     let output1 = session
         .command("sudo")
@@ -382,7 +423,7 @@ async fn cm5_short_push_power_button(
     log::info!("{}", String::from_utf8_lossy(&output1.stdout));
 
     // Sleep for 1 second
-    sleep(std::time::Duration::from_secs(1));
+    sleep(Duration::from_secs(1)).await;
 
     // Set GPIO high
     let output2 = session
@@ -398,52 +439,51 @@ async fn cm5_short_push_power_button(
     Ok(())
 }
 
-/// Asynchronously executes the `ping` command with the specified hostname,
-/// sending a single ICMP packet (`-c 1`) and waiting up to 1 second for a response (`-W 1`).
-///
-/// # Arguments
-///
-/// * `hostname` - The target host to ping.
-///
-/// # Returns
-///
-/// Returns a `tokio::process::Output` future containing the output of the ping command.
-///
-/// # Errors
-///
-/// Returns an error if the command fails to start or complete.
-///
-/// # Example
+/// Sends one ICMP echo to `hostname` from this machine, returning true if it answered.
+pub async fn ping(hostname: &str) -> bool {
+    if let Ok(output) = tokio::process::Command::new("ping")
+        .arg("-c")
+        .arg("1")
+        .arg("-W")
+        .arg("1")
+        .arg(hostname)
+        .output()
+        .await
+    {
+        return output.status.success();
+    }
+    false
+}
+
+/// Returns true if the node is reachable (powered on) on any of its addresses, false otherwise.
 ///
-/// ```rust
-/// let output = tokio::process::Command::new("ping")
-///     .arg("-c")
-///     .arg("1")
-///     .arg("-W")
-///     .arg("1")
-///     .arg(hostname)
-///     .output()
-///     .await;
-/// ```
-/// Returns true if the node is reachable (powered on), false otherwise.
-async fn power_status(config: &Config, slot_number: &i32) -> bool {
-    if let Some(node) = config
+/// A node behind a `proxy_jump` is pinged from the jump host, since it may not be routable
+/// from here.
+pub async fn power_status(config: &Config, slot_number: &i32) -> bool {
+    let Some(node) = config
         .cluster
         .nodes
         .iter()
         .find(|n| n.slot_number == *slot_number)
-    {
-        let hostname = &node.hostname;
-        if let Ok(output) = tokio::process::Command::new("ping")
-            .arg("-c")
-            .arg("1")
-            .arg("-W")
-            .arg("1")
-            .arg(hostname)
-            .output()
-            .await
-        {
-            return output.status.success();
+    else {
+        return false;
+    };
+    let jump_host = match ssh::connect_jump_host(config, node).await {
+        Ok(jump_host) => jump_host,
+        Err(e) => {
+            log::warn!("Cannot reach the jump host of slot {slot_number}: {e}");
+            return false;
+        }
+    };
+    for address in config.addresses(node) {
+        let answered = match &jump_host {
+            Some(session) => ssh::run(session, &format!("ping -c 1 -W 1 {}", ssh::quote(address)))
+                .await
+                .is_ok(),
+            None => ping(address).await,
+        };
+        if answered {
+            return true;
         }
     }
     false
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use crate::{
+    cli::{FanMode, FanSpeed},
+    commands::{fan, power, thermal},
+    config::{Config, Node},
+    ssh,
+};
+
+/// Watches the temperatures of `nodes` until Ctrl-C.
+///
+/// A node above its model's warning threshold forces the controller fan to full speed
+/// until every node is back under its warning threshold. A node above its critical
+/// threshold is shut down through the regular shutdown path.
+pub async fn protect(config: &Config, nodes: &[&Node]) -> anyhow::Result<()> {
+    let max_state = {
+        let session = ssh::connect_controller(config).await?;
+        fan::discover_fan(&session, config).await?.max_state
+    };
+
+    let mut fan_forced = false;
+    let mut ticker =
+        tokio::time::interval(Duration::from_secs(config.protection.interval_secs.max(1)));
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = ticker.tick() => {}
+        }
+
+        let mut warning = false;
+        for reading in thermal::read_cluster_temperatures(config, nodes).await {
+            let Some(&node) = nodes.iter().find(|n| n.slot_number == reading.slot_number) else {
+                continue;
+            };
+            let thresholds = config.protection.thresholds(&node.model);
+            log::debug!(
+                "Slot {} ({}) at {:.1}°C",
+                node.slot_number,
+                node.hostname,
+                reading.temperature
+            );
+
+            if reading.temperature >= thresholds.critical {
+                if node.slot_number == 1 {
+                    log::error!(
+                        "Controller ({}) at {:.1}°C is above its critical threshold of {:.1}°C and cannot be shut down by this tool!",
+                        node.hostname,
+                        reading.temperature,
+                        thresholds.critical
+                    );
+                } else {
+                    log::error!(
+                        "Slot {} ({}) at {:.1}°C is above its critical threshold of {:.1}°C, shutting it down",
+                        node.slot_number,
+                        node.hostname,
+                        reading.temperature,
+                        thresholds.critical
+                    );
+                    power::shutdown_node(config, node).await;
+                }
+            }
+
+            if reading.temperature >= thresholds.warning {
+                warning = true;
+                if !fan_forced {
+                    log::warn!(
+                        "Slot {} ({}) at {:.1}°C is above its warning threshold of {:.1}°C, forcing the fan to full speed",
+                        node.slot_number,
+                        node.hostname,
+                        reading.temperature,
+                        thresholds.warning
+                    );
+                }
+            }
+        }
+
+        if warning && !fan_forced {
+            match force_full_speed(config, max_state).await {
+                Ok(()) => fan_forced = true,
+                Err(e) => log::error!("Failed to force the fan to full speed: {}", e),
+            }
+        } else if !warning && fan_forced {
+            log::info!(
+                "All nodes are back under their warning threshold, restoring the automatic fan mode"
+            );
+            match fan::fan_mode(config, &FanMode::Enabled).await {
+                Ok(()) => fan_forced = false,
+                Err(e) => log::error!("Failed to restore the automatic fan mode: {}", e),
+            }
+        }
+    }
+
+    if fan_forced {
+        log::info!("Restoring the controller's automatic fan mode");
+        fan::fan_mode(config, &FanMode::Enabled).await?;
+    }
+    Ok(())
+}
+
+async fn force_full_speed(config: &Config, max_state: i32) -> anyhow::Result<()> {
+    fan::fan_mode(config, &FanMode::Disabled).await?;
+    fan::fan_speed(config, &FanSpeed(max_state)).await
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use serde_derive::Serialize;
+
+use crate::{
+    config::{Config, Model, Node},
+    ssh,
+};
+
+/// Quick health readings of a node, gathered in a single SSH round trip.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeVitals {
+    /// Hottest thermal zone, in degrees Celsius.
+    pub temperature: Option<f64>,
+    /// 1, 5 and 15 minute load averages.
+    pub load: Option<[f64; 3]>,
+}
+
+pub async fn read_vitals(config: &Config, node: &Node) -> anyhow::Result<NodeVitals> {
+    let session = ssh::connect(config, node).await?;
+    let output = session
+        .command("sh")
+        .arg("-c")
+        .arg("cat /proc/loadavg; cat /sys/class/thermal/thermal_zone*/temp 2>/dev/null; true")
+        .output()
+        .await?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+
+    let load = lines.next().and_then(|line| {
+        let mut fields = line.split_whitespace().map(|f| f.parse::<f64>().ok());
+        Some([fields.next()??, fields.next()??, fields.next()??])
+    });
+    let temperature = lines
+        .filter_map(|line| line.trim().parse::<i64>().ok())
+        .max()
+        .map(|millicelsius| millicelsius as f64 / 1000.0);
+    Ok(NodeVitals { temperature, load })
+}
+
+/// Detailed state of a powered node, shown by `power status --details`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeDetails {
+    pub uptime_secs: Option<u64>,
+    /// 1, 5 and 15 minute load averages.
+    pub load: Option<[f64; 3]>,
+    pub memory_total_mib: Option<u64>,
+    pub memory_available_mib: Option<u64>,
+    /// Hottest thermal zone, in degrees Celsius.
+    pub temperature: Option<f64>,
+    /// `vcgencmd get_throttled`, only read on Raspberry Pi compute modules.
+    pub throttled: Option<Throttling>,
+    pub kernel: Option<String>,
+    pub os_release: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Throttling {
+    /// Raw bit field, e.g. `0x50005`.
+    pub raw: String,
+    pub flags: Vec<&'static str>,
+}
+
+/// Meaning of the `get_throttled` bits, see the Raspberry Pi `vcgencmd` documentation.
+const THROTTLE_FLAGS: [(u32, &str); 8] = [
+    (0, "under-voltage"),
+    (1, "frequency-capped"),
+    (2, "throttled"),
+    (3, "soft-temperature-limit"),
+    (16, "under-voltage-occurred"),
+    (17, "frequency-capping-occurred"),
+    (18, "throttling-occurred"),
+    (19, "soft-temperature-limit-occurred"),
+];
+
+/// Reads everything in one SSH round trip; the output is split in `@section` blocks.
+pub async fn read_details(config: &Config, node: &Node) -> anyhow::Result<NodeDetails> {
+    let mut script = String::from(
+        "echo @uptime; cat /proc/uptime; \
+         echo @loadavg; cat /proc/loadavg; \
+         echo @meminfo; grep -E '^(MemTotal|MemAvailable):' /proc/meminfo; \
+         echo @temp; cat /sys/class/thermal/thermal_zone*/temp 2>/dev/null; \
+         echo @kernel; uname -r; \
+         echo @os; (. /etc/os-release && echo \"$PRETTY_NAME\") 2>/dev/null; ",
+    );
+    if matches!(node.model, Model::CM4 | Model::CM5) {
+        script.push_str("echo @throttled; vcgencmd get_throttled 2>/dev/null; ");
+    }
+    script.push_str("true");
+
+    let session = ssh::connect(config, node).await?;
+    let output = session.command("sh").arg("-c").arg(script).output().await?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let sections = sections(&stdout);
+    let section = |name: &str| sections.get(name).map(Vec::as_slice).unwrap_or_default();
+    let first = |name: &str| section(name).first().copied();
+
+    Ok(NodeDetails {
+        uptime_secs: first("uptime")
+            .and_then(|l| l.split_whitespace().next()?.parse::<f64>().ok())
+            .map(|secs| secs as u64),
+        load: first("loadavg").and_then(|line| {
+            let mut fields = line.split_whitespace().map(|f| f.parse::<f64>().ok());
+            Some([fields.next()??, fields.next()??, fields.next()??])
+        }),
+        memory_total_mib: meminfo_mib(section("meminfo"), "MemTotal:"),
+        memory_available_mib: meminfo_mib(section("meminfo"), "MemAvailable:"),
+        temperature: section("temp")
+            .iter()
+            .filter_map(|line| line.parse::<i64>().ok())
+            .max()
+            .map(|millicelsius| millicelsius as f64 / 1000.0),
+        throttled: first("throttled").and_then(parse_throttled),
+        kernel: first("kernel").map(str::to_owned),
+        os_release: first("os").map(str::to_owned),
+    })
+}
+
+/// Splits the output of a script printing `@name` lines before each part into the trimmed,
+/// non-empty lines of every part.
+pub fn sections(stdout: &str) -> HashMap<&str, Vec<&str>> {
+    let mut sections: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut current = "";
+    for line in stdout.lines() {
+        match line.strip_prefix('@') {
+            Some(name) => current = name,
+            None if !line.trim().is_empty() => {
+                sections.entry(current).or_default().push(line.trim())
+            }
+            None => {}
+        }
+    }
+    sections
+}
+
+/// Reads the `/proc/meminfo` entry `key`, e.g. `MemTotal:`, in MiB.
+pub fn meminfo_mib(lines: &[&str], key: &str) -> Option<u64> {
+    lines.iter().find_map(|line| {
+        let kib = line.strip_prefix(key)?.trim().strip_suffix("kB")?;
+        kib.trim().parse::<u64>().ok().map(|kib| kib / 1024)
+    })
+}
+
+/// Parses `throttled=0x50005`.
+fn parse_throttled(line: &str) -> Option<Throttling> {
+    let raw = line.strip_prefix("throttled=")?;
+    let bits = u32::from_str_radix(raw.trim_start_matches("0x"), 16).ok()?;
+    Some(Throttling {
+        raw: raw.to_owned(),
+        flags: THROTTLE_FLAGS
+            .iter()
+            .filter(|(bit, _)| bits & (1 << bit) != 0)
+            .map(|(_, name)| *name)
+            .collect(),
+    })
+}
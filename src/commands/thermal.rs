@@ -0,0 +1,60 @@
+use futures::future::join_all;
+
+use crate::{
+    commands::power,
+    config::{Config, Node},
+    ssh,
+};
+
+/// Hottest temperature reported by one node, in degrees Celsius.
+#[derive(Debug, Clone)]
+pub struct NodeTemperature {
+    pub slot_number: i32,
+    pub hostname: String,
+    pub temperature: f64,
+}
+
+/// Reads every thermal zone of `node` over SSH and returns the highest value.
+pub async fn read_node_temperature(config: &Config, node: &Node) -> anyhow::Result<f64> {
+    let session = ssh::connect(config, node).await?;
+    let output = session
+        .command("sh")
+        .arg("-c")
+        .arg("cat /sys/class/thermal/thermal_zone*/temp")
+        .output()
+        .await?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse::<i64>().ok())
+        .max()
+        .map(|millicelsius| millicelsius as f64 / 1000.0)
+        .ok_or_else(|| anyhow::anyhow!("No thermal zone readable on {}", node.hostname))
+}
+
+/// Reads the temperature of every powered node among `nodes` concurrently.
+///
+/// Nodes that are off are skipped silently; nodes that are on but cannot be read are
+/// logged and left out of the result.
+pub async fn read_cluster_temperatures(config: &Config, nodes: &[&Node]) -> Vec<NodeTemperature> {
+    let readings = nodes.iter().map(|&node| async move {
+        if !power::power_status(config, &node.slot_number).await {
+            return None;
+        }
+        match read_node_temperature(config, node).await {
+            Ok(temperature) => Some(NodeTemperature {
+                slot_number: node.slot_number,
+                hostname: node.hostname.clone(),
+                temperature,
+            }),
+            Err(e) => {
+                log::warn!(
+                    "Failed to read temperature of node {}: {}",
+                    node.slot_number,
+                    e
+                );
+                None
+            }
+        }
+    });
+    join_all(readings).await.into_iter().flatten().collect()
+}
@@ -0,0 +1,187 @@
+use std::{
+    future::Future,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use futures::{StreamExt, stream};
+use openssh::{Session, Stdio};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    cli::{PullArgs, PushArgs},
+    commands::power,
+    config::{Config, Node},
+    output::{NodeResult, OperationReport, Outcome},
+    ssh,
+};
+
+/// Copies a local file to the selected nodes that are on, `parallel` nodes at a time.
+///
+/// The file is streamed to a temporary file, then copied over `remote` with the local
+/// file's mode unless `--mode` is given, and its SHA-256 is checked on the node.
+pub async fn push(
+    config: &Config,
+    nodes: &[&Node],
+    args: &PushArgs,
+) -> anyhow::Result<OperationReport> {
+    let content = std::fs::read(&args.local)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {e}", args.local.display()))?;
+    let checksum = sha256(&content);
+    let mode = match &args.mode {
+        Some(mode) => u32::from_str_radix(mode, 8)
+            .map_err(|_| anyhow::anyhow!("Invalid mode '{mode}', expected octal like 644"))?,
+        None => std::fs::metadata(&args.local)?.permissions().mode() & 0o7777,
+    };
+    let remote = if args.remote.ends_with('/') {
+        let name = args
+            .local
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("{} is not a file", args.local.display()))?;
+        format!("{}{}", args.remote, name.to_string_lossy())
+    } else {
+        args.remote.clone()
+    };
+    let sudo = if args.sudo || args.owner.is_some() {
+        "sudo "
+    } else {
+        ""
+    };
+
+    let (content, checksum, remote) = (&content, &checksum, &remote);
+    let report = for_each_node(config, nodes, args.parallel, "push", |node| async move {
+        let session = ssh::connect(config, node).await?;
+        let temporary = upload(&session, content).await?;
+        let target = ssh::quote(remote);
+        let mut script = format!(
+            "trap \"rm -f {temporary}\" EXIT; set -e; \
+             {sudo}cp {temporary} {target}; {sudo}chmod {mode:o} {target}; "
+        );
+        if let Some(owner) = &args.owner {
+            script += &format!("{sudo}chown {} {target}; ", ssh::quote(owner));
+        }
+        script += &format!("{sudo}sha256sum {target}");
+        let remote_checksum = ssh::run(&session, &script).await?;
+        verify(checksum, &remote_checksum)?;
+        Ok(format!("{remote} sha256 {}", &checksum[..12]))
+    })
+    .await;
+    Ok(report)
+}
+
+/// Copies `remote` from the selected nodes that are on to `<local>/<hostname>/<file name>`,
+/// checking that its SHA-256 matches the node's. Nodes without a hostname are stored under
+/// their address.
+pub async fn pull(config: &Config, nodes: &[&Node], args: &PullArgs) -> OperationReport {
+    let sudo = if args.sudo { "sudo " } else { "" };
+    let name = Path::new(&args.remote)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_owned());
+    let name = &name;
+    for_each_node(config, nodes, args.parallel, "pull", |node| async move {
+        let session = ssh::connect(config, node).await?;
+        let source = ssh::quote(&args.remote);
+        let output = session
+            .command("sh")
+            .arg("-c")
+            .arg(format!("{sudo}cat {source}"))
+            .output()
+            .await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Cannot read {}: {}",
+                args.remote,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let remote_checksum = ssh::run(&session, &format!("{sudo}sha256sum {source}")).await?;
+        let checksum = sha256(&output.stdout);
+        verify(&checksum, &remote_checksum)?;
+
+        let destination: PathBuf = args.local.join(config.directory_name(node)).join(name);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&destination, &output.stdout)?;
+        Ok(format!(
+            "{} sha256 {}",
+            destination.display(),
+            &checksum[..12]
+        ))
+    })
+    .await
+}
+
+/// Runs `transfer` on the nodes that are on, `parallel` at a time, reporting its message.
+async fn for_each_node<'a, F, Fut>(
+    config: &Config,
+    nodes: &[&'a Node],
+    parallel: u64,
+    action: &str,
+    transfer: F,
+) -> OperationReport
+where
+    F: Fn(&'a Node) -> Fut,
+    Fut: Future<Output = anyhow::Result<String>>,
+{
+    let transfer = &transfer;
+    let mut results: Vec<NodeResult> = stream::iter(nodes)
+        .map(|&node| async move {
+            if !power::power_status(config, &node.slot_number).await {
+                return NodeResult::new(node, Outcome::Skipped, "powered off");
+            }
+            match transfer(node).await {
+                Ok(message) => NodeResult::new(node, Outcome::Ok, message),
+                Err(e) => NodeResult::new(node, Outcome::Failed, e.to_string()),
+            }
+        })
+        .buffer_unordered(parallel.max(1) as usize)
+        .collect()
+        .await;
+    results.sort_by_key(|r| r.slot_number);
+    OperationReport {
+        action: action.to_owned(),
+        results,
+    }
+}
+
+/// Streams `content` into a new temporary file on the node and returns its quoted path.
+async fn upload(session: &Session, content: &[u8]) -> anyhow::Result<String> {
+    let mut child = session
+        .command("sh")
+        .arg("-c")
+        .arg("t=$(mktemp) && cat > \"$t\" && echo \"$t\"")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .await?;
+    if let Some(mut stdin) = child.stdin().take() {
+        stdin.write_all(content).await?;
+        stdin.shutdown().await?;
+    }
+    let output = child.wait_with_output().await?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if !output.status.success() || path.is_empty() {
+        anyhow::bail!(
+            "Upload failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(ssh::quote(&path))
+}
+
+/// Compares `checksum` with the first field of `sha256sum`'s output.
+fn verify(checksum: &str, sha256sum: &str) -> anyhow::Result<()> {
+    match sha256sum.split_whitespace().next() {
+        Some(remote) if remote == checksum => Ok(()),
+        Some(remote) => anyhow::bail!("Checksum mismatch: local {checksum}, remote {remote}"),
+        None => anyhow::bail!("sha256sum printed nothing"),
+    }
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
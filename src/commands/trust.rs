@@ -0,0 +1,119 @@
+use std::{io::Write, path::Path, process::Stdio};
+
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    config::{Config, Node},
+    ssh,
+};
+
+/// Fetches the host keys of `node` and pins them in the tool's
+/// known_hosts file, replacing any key recorded before for its addresses (e.g. after the
+/// node was reflashed).
+pub async fn trust(config: &Config, node: &Node) -> anyhow::Result<()> {
+    let known_hosts_file = config
+        .known_hosts_file
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("No known_hosts file configured"))?;
+    let settings = config.ssh_settings(node);
+    let port = settings.port.unwrap_or(22);
+    let through_controller = match ssh::jump_host(config, node, &settings)? {
+        None => false,
+        Some(_) if settings.proxy_jump.as_deref() == Some("controller") => true,
+        Some(jump_host) => anyhow::bail!(
+            "Cannot fetch host keys through proxy_jump '{jump_host}', only 'controller' is supported"
+        ),
+    };
+
+    for address in config.addresses(node) {
+        let keys = scan(config, through_controller, address, port).await?;
+        if keys.is_empty() {
+            anyhow::bail!("No host key received from {address}");
+        }
+
+        // ssh records non-default ports as `[host]:port`.
+        let entry = if port == 22 {
+            address.to_owned()
+        } else {
+            format!("[{address}]:{port}")
+        };
+        forget(known_hosts_file, &entry).await?;
+
+        if let Some(parent) = known_hosts_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(known_hosts_file)?;
+        for key in &keys {
+            writeln!(file, "{key}")?;
+        }
+
+        println!(
+            "Pinned host keys of {entry} in {}:",
+            known_hosts_file.display()
+        );
+        print!("{}", fingerprints(&keys).await?);
+    }
+    Ok(())
+}
+
+/// Runs `ssh-keyscan` against `address`, locally or from the controller, and returns the
+/// known_hosts lines it printed.
+async fn scan(
+    config: &Config,
+    through_controller: bool,
+    address: &str,
+    port: u16,
+) -> anyhow::Result<Vec<String>> {
+    let args = ["-T", "5", "-p", &port.to_string(), address].map(str::to_owned);
+    let output = if through_controller {
+        ssh::connect_controller(config)
+            .await?
+            .command("ssh-keyscan")
+            .args(&args)
+            .output()
+            .await?
+    } else {
+        tokio::process::Command::new("ssh-keyscan")
+            .args(&args)
+            .output()
+            .await?
+    };
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Removes every key recorded for `entry`.
+async fn forget(known_hosts_file: &Path, entry: &str) -> anyhow::Result<()> {
+    if !known_hosts_file.exists() {
+        return Ok(());
+    }
+    let output = tokio::process::Command::new("ssh-keygen")
+        .arg("-R")
+        .arg(entry)
+        .arg("-f")
+        .arg(known_hosts_file)
+        .output()
+        .await?;
+    log::debug!("{}", String::from_utf8_lossy(&output.stdout));
+    Ok(())
+}
+
+async fn fingerprints(keys: &[String]) -> anyhow::Result<String> {
+    let mut child = tokio::process::Command::new("ssh-keygen")
+        .args(["-l", "-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(keys.join("\n").as_bytes()).await?;
+        stdin.write_all(b"\n").await?;
+    }
+    let output = child.wait_with_output().await?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
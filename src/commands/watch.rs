@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    io::{IsTerminal, Write},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+use futures::future::join_all;
+use serde_derive::Serialize;
+
+use crate::{
+    commands::power::{self, NodeStatus, PowerState},
+    config::{Config, Node},
+    output::OutputFormat,
+};
+
+/// A node changing power state, emitted as one JSON line per event. The first probe emits
+/// one event per node with no `previous` state.
+#[derive(Debug, Serialize)]
+pub struct PowerEvent {
+    pub timestamp: DateTime<Local>,
+    pub slot_number: i32,
+    pub hostname: String,
+    pub previous: Option<PowerState>,
+    pub power: PowerState,
+}
+
+/// Re-probes `nodes` every `interval` until Ctrl-C, either redrawing a status table in
+/// place (`table`, appended instead when stdout is not a terminal) or printing transitions
+/// as NDJSON (`json`).
+pub async fn watch_power_status(
+    config: &Config,
+    nodes: &[&Node],
+    interval: Duration,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    if !matches!(format, OutputFormat::Table | OutputFormat::Json) {
+        anyhow::bail!("--watch only supports --output table or json");
+    }
+    let terminal = std::io::stdout().is_terminal();
+    let mut last: HashMap<i32, (PowerState, Option<DateTime<Local>>)> = HashMap::new();
+    let mut drawn_lines = 0;
+
+    let mut ticker = tokio::time::interval(interval);
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = ticker.tick() => {}
+        }
+
+        let statuses = join_all(nodes.iter().map(|node| power::node_status(config, node))).await;
+        let now = Local::now();
+        let mut changed = Vec::new();
+        for status in &statuses {
+            let previous = last.get(&status.slot_number).map(|(state, _)| *state);
+            if previous == Some(status.power) {
+                continue;
+            }
+            // The first probe only establishes the state, it is not a transition.
+            let since = previous.map(|_| now);
+            last.insert(status.slot_number, (status.power, since));
+            changed.push(status.slot_number);
+            if format == OutputFormat::Json {
+                let event = PowerEvent {
+                    timestamp: now,
+                    slot_number: status.slot_number,
+                    hostname: status.hostname.clone(),
+                    previous,
+                    power: status.power,
+                };
+                println!("{}", serde_json::to_string(&event)?);
+            }
+        }
+
+        if format == OutputFormat::Table {
+            let lines = render(&statuses, &last, &changed, interval, now, terminal);
+            let mut stdout = std::io::stdout().lock();
+            if terminal {
+                // Move back to the top of the previous table and overwrite it.
+                if drawn_lines > 0 {
+                    write!(stdout, "\x1b[{drawn_lines}A")?;
+                }
+                for line in &lines {
+                    writeln!(stdout, "\x1b[2K{line}")?;
+                }
+                drawn_lines = lines.len();
+            } else {
+                // Piped or redirected output gets one plain table per probe.
+                for line in &lines {
+                    writeln!(stdout, "{line}")?;
+                }
+                writeln!(stdout)?;
+            }
+        }
+        std::io::stdout().flush()?;
+    }
+    Ok(())
+}
+
+/// Compact status table. Rows that changed in the latest probe are highlighted in the
+/// color of their new state.
+fn render(
+    statuses: &[NodeStatus],
+    last: &HashMap<i32, (PowerState, Option<DateTime<Local>>)>,
+    changed: &[i32],
+    interval: Duration,
+    now: DateTime<Local>,
+    color: bool,
+) -> Vec<String> {
+    let hostname_width = statuses
+        .iter()
+        .map(|s| s.hostname.len())
+        .max()
+        .unwrap_or(0)
+        .max("HOSTNAME".len());
+    let mut lines = vec![
+        format!(
+            "Every {}s, last probe {} (Ctrl-C to stop)",
+            interval.as_secs(),
+            now.format("%H:%M:%S")
+        ),
+        format!(
+            "{:<4}  {:<hostname_width$}  {:<5}  {:<5}  CHANGED",
+            "SLOT", "HOSTNAME", "MODEL", "POWER"
+        ),
+    ];
+    for status in statuses {
+        let since = last
+            .get(&status.slot_number)
+            .and_then(|(_, since)| *since)
+            .map(|since| since.format("%H:%M:%S").to_string())
+            .unwrap_or_else(|| "-".to_owned());
+        let power = format!("{:?}", status.power).to_lowercase();
+        let line = format!(
+            "{:<4}  {:<hostname_width$}  {:<5}  {:<5}  {since}",
+            status.slot_number,
+            status.hostname,
+            format!("{:?}", status.model),
+            power
+        );
+        let highlighted = color && changed.contains(&status.slot_number) && since != "-";
+        lines.push(match (highlighted, status.power) {
+            (true, PowerState::On) => format!("\x1b[1;32m{line}\x1b[0m"),
+            (true, PowerState::Off) => format!("\x1b[1;31m{line}\x1b[0m"),
+            (false, _) => line,
+        });
+    }
+    lines
+}
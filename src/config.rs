@@ -0,0 +1,1022 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    net::IpAddr,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::NaiveTime;
+use serde_derive::{Deserialize, Serialize};
+use toml_edit::ImDocument;
+
+/// Number of slots on the NanoCluster board.
+pub const SLOT_COUNT: i32 = 7;
+
+/// Name given to the cluster of a file written before contexts existed.
+pub const DEFAULT_CONTEXT: &str = "default";
+
+/// Content of the configuration file: one [`Config`] per named cluster.
+///
+/// Files holding a single top-level [`Config`] are still accepted and exposed as the
+/// [`DEFAULT_CONTEXT`] context.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ConfigFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_context: Option<String>,
+    #[serde(default)]
+    pub contexts: BTreeMap<String, Config>,
+    /// Whether the file uses the single-cluster layout.
+    #[serde(skip)]
+    pub legacy: bool,
+}
+
+impl ConfigFile {
+    /// Picks the context named `name`, falling back to `current_context` and then to the
+    /// only context of the file.
+    pub fn select(&self, name: Option<&str>) -> anyhow::Result<(&str, &Config)> {
+        let name = match name.or(self.current_context.as_deref()) {
+            Some(name) => name,
+            None if self.contexts.len() == 1 => self.contexts.keys().next().unwrap(),
+            None => anyhow::bail!(
+                "Several contexts are defined ({}), pick one with --context or `config use-context`",
+                self.context_names()
+            ),
+        };
+        self.contexts
+            .get_key_value(name)
+            .map(|(name, config)| (name.as_str(), config))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown context '{name}', available contexts: {}",
+                    self.context_names()
+                )
+            })
+    }
+
+    fn context_names(&self) -> String {
+        self.contexts
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Serializes the file, keeping the single-cluster layout when it still fits.
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        if self.legacy
+            && self.current_context.is_none()
+            && self.contexts.len() == 1
+            && let Some(config) = self.contexts.get(DEFAULT_CONTEXT)
+        {
+            return Ok(toml::to_string(config)?);
+        }
+        Ok(toml::to_string(self)?)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    pub ssh_username: String,
+    pub cluster: Cluster,
+    #[serde(default)]
+    pub fan: FanConfig,
+    #[serde(default)]
+    pub protection: ProtectionConfig,
+    /// known_hosts file managed by this tool, set at load time.
+    #[serde(skip)]
+    pub known_hosts_file: Option<PathBuf>,
+    /// Host key policy given on the command line, overriding every node's setting.
+    #[serde(skip)]
+    pub known_hosts_override: Option<KnownHostsPolicy>,
+}
+
+/// How the controller's fan is located under `/sys/class/thermal`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FanConfig {
+    /// `type` of the cooling device driving the fan.
+    pub cooling_device_type: String,
+    /// `type` of the thermal zone whose mode toggles the automatic fan control.
+    /// When unset, the zone bound to the cooling device is used.
+    pub thermal_zone_type: Option<String>,
+    /// Seconds between two temperature polls in `FANAUTO` mode.
+    pub interval_secs: u64,
+    /// Degrees Celsius the hottest node must drop below a curve point before the fan slows down.
+    pub hysteresis: f64,
+    /// Minimum number of seconds the fan keeps a state before it may slow down.
+    pub min_dwell_secs: u64,
+    /// Temperature to fan state mapping used in `FANAUTO` mode.
+    pub curve: Vec<CurvePoint>,
+    /// Quiet hours capping the fan state in `FANAUTO` mode.
+    pub schedule: FanSchedule,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FanSchedule {
+    /// Hottest node temperature (degrees Celsius) above which the quiet hours cap is ignored.
+    pub override_temperature: f64,
+    pub windows: Vec<QuietWindow>,
+}
+
+/// Local time window, as `HH:MM`, during which the fan state is capped at `max_state`.
+/// Windows may wrap around midnight (e.g. `22:00` to `07:00`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietWindow {
+    pub start: String,
+    pub end: String,
+    pub max_state: i32,
+}
+
+impl Default for FanSchedule {
+    fn default() -> Self {
+        FanSchedule {
+            override_temperature: 70.0,
+            windows: vec![],
+        }
+    }
+}
+
+/// Fan state to apply once the hottest node reaches `temperature` (degrees Celsius).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurvePoint {
+    pub temperature: f64,
+    pub state: i32,
+}
+
+impl Default for FanConfig {
+    fn default() -> Self {
+        FanConfig {
+            cooling_device_type: "pwm-fan".to_owned(),
+            thermal_zone_type: None,
+            interval_secs: 15,
+            hysteresis: 3.0,
+            min_dwell_secs: 60,
+            curve: vec![
+                CurvePoint {
+                    temperature: 45.0,
+                    state: 1,
+                },
+                CurvePoint {
+                    temperature: 55.0,
+                    state: 2,
+                },
+                CurvePoint {
+                    temperature: 65.0,
+                    state: 3,
+                },
+                CurvePoint {
+                    temperature: 75.0,
+                    state: 4,
+                },
+            ],
+            schedule: FanSchedule::default(),
+        }
+    }
+}
+
+/// Per-model temperature limits enforced by the `PROTECT` mode.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtectionConfig {
+    /// Seconds between two temperature polls.
+    pub interval_secs: u64,
+    pub cm4: Thresholds,
+    pub cm5: Thresholds,
+    pub lpi3h: Thresholds,
+}
+
+/// Temperatures in degrees Celsius.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Thresholds {
+    /// The fan is forced to full speed above this temperature.
+    pub warning: f64,
+    /// The node is shut down above this temperature.
+    pub critical: f64,
+}
+
+impl ProtectionConfig {
+    pub fn thresholds(&self, model: &Model) -> &Thresholds {
+        match model {
+            Model::CM4 => &self.cm4,
+            Model::CM5 => &self.cm5,
+            Model::LPI3H => &self.lpi3h,
+        }
+    }
+}
+
+impl Default for ProtectionConfig {
+    fn default() -> Self {
+        ProtectionConfig {
+            interval_secs: 10,
+            cm4: Thresholds {
+                warning: 75.0,
+                critical: 85.0,
+            },
+            cm5: Thresholds {
+                warning: 75.0,
+                critical: 85.0,
+            },
+            lpi3h: Thresholds {
+                warning: 80.0,
+                critical: 95.0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cluster {
+    /// Address of the controller, used when its node has no `ip_address` of its own.
+    #[serde(default, alias = "_ip_address")]
+    pub ip_address: String,
+    /// How node addresses are picked, unless a node overrides it.
+    #[serde(default)]
+    pub address_policy: AddressPolicy,
+    /// SSH defaults for every node of the cluster.
+    #[serde(default)]
+    pub ssh: SshSettings,
+    /// Named sets of nodes, listed by hostname, matched by `group=` node selectors.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, Vec<String>>,
+    pub nodes: Vec<Node>,
+}
+
+/// SSH connection settings. Unset values fall back to the cluster's settings, then to
+/// `ssh_username` for the user and to ssh's own defaults for the rest.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SshSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_file: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub known_hosts: Option<KnownHostsPolicy>,
+    /// Host to jump through, as `[user@]host[:port]`, or `controller` for the node in slot 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_jump: Option<String>,
+    /// known_hosts file to check and record host keys in, instead of the user's.
+    #[serde(skip)]
+    pub known_hosts_file: Option<PathBuf>,
+}
+
+impl SshSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == SshSettings::default()
+    }
+
+    /// These settings, with unset values taken from `defaults`.
+    pub fn or(&self, defaults: &SshSettings) -> SshSettings {
+        SshSettings {
+            user: self.user.clone().or_else(|| defaults.user.clone()),
+            port: self.port.or(defaults.port),
+            identity_file: self
+                .identity_file
+                .clone()
+                .or_else(|| defaults.identity_file.clone()),
+            connect_timeout_secs: self.connect_timeout_secs.or(defaults.connect_timeout_secs),
+            known_hosts: self.known_hosts.or(defaults.known_hosts),
+            proxy_jump: self
+                .proxy_jump
+                .clone()
+                .or_else(|| defaults.proxy_jump.clone()),
+            known_hosts_file: self
+                .known_hosts_file
+                .clone()
+                .or_else(|| defaults.known_hosts_file.clone()),
+        }
+    }
+}
+
+/// What ssh does with host keys it does not know yet.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum KnownHostsPolicy {
+    /// Refuse unknown hosts.
+    Strict,
+    /// Record unknown hosts' keys, refuse changed keys.
+    #[default]
+    Add,
+    /// Accept any key.
+    Accept,
+}
+
+/// Which of a node's `ip_address` and `hostname` is used to reach it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AddressPolicy {
+    /// The IP address when set, the hostname otherwise.
+    PreferIp,
+    /// The hostname when set, the IP address otherwise.
+    #[default]
+    PreferHostname,
+    /// The IP address first, then the hostname if the IP address does not answer.
+    TryBoth,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Node {
+    /// IPv4 or IPv6 address, empty when unknown.
+    #[serde(default, alias = "_ip_address")]
+    pub ip_address: String,
+    pub hostname: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_policy: Option<AddressPolicy>,
+    /// Overrides of the cluster's SSH settings for this node.
+    #[serde(default, skip_serializing_if = "SshSettings::is_empty")]
+    pub ssh: SshSettings,
+    pub model: Model,
+    pub slot_number: i32,
+    /// Free-form `key = value` tags, matched by `label=` node selectors.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Model {
+    CM5,
+    CM4,
+    LPI3H,
+}
+
+impl Model {
+    /// Recognizes a module from the content of `/proc/device-tree/model`.
+    pub fn from_device_tree(model: &str) -> Option<Model> {
+        let model = model.to_ascii_lowercase();
+        if model.contains("compute module 5") {
+            Some(Model::CM5)
+        } else if model.contains("compute module 4") {
+            Some(Model::CM4)
+        } else if ["lpi3h", "licheepi 3h", "lichee pi 3h"]
+            .iter()
+            .any(|name| model.contains(name))
+        {
+            Some(Model::LPI3H)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "CM5" => Ok(Model::CM5),
+            "CM4" => Ok(Model::CM4),
+            "LPI3H" => Ok(Model::LPI3H),
+            _ => Err(format!("Unknown model '{s}', expected CM4, CM5 or LPI3H")),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            ssh_username: "".to_owned(),
+            cluster: Cluster {
+                ip_address: "".to_owned(),
+                address_policy: AddressPolicy::default(),
+                ssh: SshSettings::default(),
+                groups: BTreeMap::new(),
+                nodes: vec![
+                    Node {
+                        ip_address: "".to_owned(),
+                        hostname: "".to_owned(),
+                        address_policy: None,
+                        ssh: SshSettings::default(),
+                        model: Model::LPI3H,
+                        slot_number: 1,
+                        labels: BTreeMap::new(),
+                    },
+                    Node {
+                        ip_address: "".to_owned(),
+                        hostname: "".to_owned(),
+                        address_policy: None,
+                        ssh: SshSettings::default(),
+                        model: Model::CM4,
+                        slot_number: 2,
+                        labels: BTreeMap::new(),
+                    },
+                    Node {
+                        ip_address: "".to_owned(),
+                        hostname: "".to_owned(),
+                        address_policy: None,
+                        ssh: SshSettings::default(),
+                        model: Model::CM4,
+                        slot_number: 3,
+                        labels: BTreeMap::new(),
+                    },
+                    Node {
+                        ip_address: "".to_owned(),
+                        hostname: "".to_owned(),
+                        address_policy: None,
+                        ssh: SshSettings::default(),
+                        model: Model::CM5,
+                        slot_number: 5,
+                        labels: BTreeMap::new(),
+                    },
+                    Node {
+                        ip_address: "".to_owned(),
+                        hostname: "".to_owned(),
+                        address_policy: None,
+                        ssh: SshSettings::default(),
+                        model: Model::CM5,
+                        slot_number: 6,
+                        labels: BTreeMap::new(),
+                    },
+                    Node {
+                        ip_address: "".to_owned(),
+                        hostname: "".to_owned(),
+                        address_policy: None,
+                        ssh: SshSettings::default(),
+                        model: Model::CM5,
+                        slot_number: 7,
+                        labels: BTreeMap::new(),
+                    },
+                ],
+            },
+            fan: FanConfig::default(),
+            protection: ProtectionConfig::default(),
+            known_hosts_file: None,
+            known_hosts_override: None,
+        }
+    }
+}
+
+impl Config {
+    /// Addresses to try, in order, to reach `node` according to its address policy.
+    pub fn addresses<'a>(&'a self, node: &'a Node) -> Vec<&'a str> {
+        let ip = if !node.ip_address.is_empty() {
+            Some(node.ip_address.as_str())
+        } else if node.slot_number == 1 && !self.cluster.ip_address.is_empty() {
+            Some(self.cluster.ip_address.as_str())
+        } else {
+            None
+        };
+        let hostname = Some(node.hostname.as_str()).filter(|h| !h.is_empty());
+        match node.address_policy.unwrap_or(self.cluster.address_policy) {
+            AddressPolicy::PreferIp => ip.or(hostname).into_iter().collect(),
+            AddressPolicy::PreferHostname => hostname.or(ip).into_iter().collect(),
+            AddressPolicy::TryBoth => ip.into_iter().chain(hostname).collect(),
+        }
+    }
+
+    /// Name of the local directory holding files of `node`: its hostname, else its address,
+    /// else `slot-<n>`.
+    pub fn directory_name(&self, node: &Node) -> String {
+        if !node.hostname.is_empty() {
+            return node.hostname.clone();
+        }
+        match self.addresses(node).first() {
+            Some(address) => address.to_string(),
+            None => format!("slot-{}", node.slot_number),
+        }
+    }
+
+    /// Names of the groups listing `node`.
+    pub fn groups_of(&self, node: &Node) -> Vec<&str> {
+        self.cluster
+            .groups
+            .iter()
+            .filter(|(_, members)| {
+                members
+                    .iter()
+                    .any(|m| m.eq_ignore_ascii_case(&node.hostname))
+            })
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// SSH settings for `node`: its own overrides on top of the cluster's, with the user
+    /// defaulting to `ssh_username`.
+    pub fn ssh_settings(&self, node: &Node) -> SshSettings {
+        let mut settings = node.ssh.or(&self.cluster.ssh);
+        settings
+            .user
+            .get_or_insert_with(|| self.ssh_username.clone());
+        if let Some(policy) = self.known_hosts_override {
+            settings.known_hosts = Some(policy);
+        }
+        settings.known_hosts_file = self.known_hosts_file.clone();
+        settings
+    }
+
+    /// The node in slot 1, which drives the power lines and the fan.
+    pub fn controller(&self) -> anyhow::Result<&Node> {
+        self.cluster
+            .nodes
+            .iter()
+            .find(|n| n.slot_number == 1)
+            .ok_or_else(|| anyhow::anyhow!("No controller node (slot_number = 1) configured"))
+    }
+}
+
+/// Loads the configuration at `path`, writing the default one first if the file does not exist.
+///
+/// Returns the parsed file together with its raw content, which [`validate`] uses to
+/// locate problems.
+pub fn load(path: &Path) -> anyhow::Result<(ConfigFile, String)> {
+    if !path.exists() {
+        confy::store_path(path, Config::default())?;
+    }
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+    let file = parse(path, &content)?;
+    Ok((file, content))
+}
+
+/// The known_hosts file this tool manages, next to the configuration file.
+pub fn known_hosts_file(config_file: &Path) -> PathBuf {
+    config_file.with_file_name("known_hosts")
+}
+
+/// Where `inventory` keeps the facts collected from the nodes, next to the configuration file.
+pub fn inventory_file(config_file: &Path) -> PathBuf {
+    config_file.with_file_name("inventory.json")
+}
+
+/// Parses either layout of the configuration file.
+pub fn parse(path: &Path, content: &str) -> anyhow::Result<ConfigFile> {
+    let error = |e: toml::de::Error| anyhow::anyhow!("{}: {e}", path.display());
+    let table: toml::Table = toml::from_str(content).map_err(error)?;
+    if table.contains_key("contexts") {
+        toml::from_str(content).map_err(error)
+    } else {
+        let config = toml::from_str(content).map_err(error)?;
+        Ok(ConfigFile {
+            current_context: None,
+            contexts: BTreeMap::from([(DEFAULT_CONTEXT.to_owned(), config)]),
+            legacy: true,
+        })
+    }
+}
+
+/// Writes `file` to `path`, creating the parent directories if needed.
+pub fn store(path: &Path, file: &ConfigFile) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, file.to_toml()?)?;
+    Ok(())
+}
+
+/// Sets `current_context` in the file at `path`, leaving the rest of it untouched.
+pub fn use_context(path: &Path, content: &str, name: &str) -> anyhow::Result<()> {
+    let mut document = content.parse::<toml_edit::DocumentMut>()?;
+    document["current_context"] = toml_edit::value(name);
+    std::fs::write(path, document.to_string())?;
+    Ok(())
+}
+
+/// Returns `content` with the `slot_number` of nodes of the context `name` replaced, nodes
+/// being given by their index in `cluster.nodes`. The rest of the file is kept as written.
+pub fn with_slot_numbers(
+    content: &str,
+    name: &str,
+    legacy: bool,
+    slots: &[(usize, i32)],
+) -> anyhow::Result<String> {
+    let mut document = content.parse::<toml_edit::DocumentMut>()?;
+    let root = if legacy {
+        document.as_item_mut()
+    } else {
+        &mut document["contexts"][name]
+    };
+    for &(index, slot_number) in slots {
+        let node = root["cluster"]["nodes"]
+            .get_mut(index)
+            .ok_or_else(|| anyhow::anyhow!("Node {index} not found in the file"))?;
+        node["slot_number"] = toml_edit::value(slot_number as i64);
+    }
+    Ok(document.to_string())
+}
+
+/// Returns `content` with `nodes` appended to the `cluster.nodes` of the context `name`.
+/// The rest of the file is kept as written.
+pub fn with_new_nodes(
+    content: &str,
+    name: &str,
+    legacy: bool,
+    nodes: &[Node],
+) -> anyhow::Result<String> {
+    let mut document = content.parse::<toml_edit::DocumentMut>()?;
+    let root = if legacy {
+        document.as_item_mut()
+    } else {
+        &mut document["contexts"][name]
+    };
+    let tables = root["cluster"]["nodes"]
+        .or_insert(toml_edit::Item::ArrayOfTables(Default::default()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| anyhow::anyhow!("cluster.nodes is not an array of tables"))?;
+    for node in nodes {
+        let table = toml::to_string(node)?.parse::<toml_edit::DocumentMut>()?;
+        tables.push(table.as_table().clone());
+    }
+    Ok(document.to_string())
+}
+
+/// A problem found in the configuration file.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub file: String,
+    /// 1-based line and column, when the offending entry could be located in the file.
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{line}:{column}: {}", self.file, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+/// One step of the path to a value in the TOML document.
+#[derive(Debug, Clone, Copy)]
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+use Segment::{Index, Key};
+
+/// Collects diagnostics, locating each one in the raw TOML through its path.
+struct Validator<'a> {
+    file: String,
+    /// Path of the validated context's table in the document.
+    base: Vec<Segment<'a>>,
+    source: &'a str,
+    document: Option<ImDocument<&'a str>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(path: &Path, source: &'a str, base: Vec<Segment<'a>>) -> Self {
+        Validator {
+            file: path.display().to_string(),
+            base,
+            source,
+            document: ImDocument::parse(source).ok(),
+            diagnostics: vec![],
+        }
+    }
+
+    /// Span of the deepest entry of `path` present in the document.
+    fn span(&self, path: &[Segment]) -> Option<Range<usize>> {
+        let mut item = self.document.as_ref()?.as_item();
+        let mut span = None;
+        for segment in self.base.iter().chain(path) {
+            let next = match segment {
+                // Older files spell some keys with a leading underscore (`_ip_address`).
+                Key(key) => item.get(*key).or_else(|| item.get(format!("_{key}"))),
+                Index(index) => item.get(*index),
+            };
+            let Some(next) = next else { break };
+            item = next;
+            span = item.span().or(span);
+        }
+        span
+    }
+
+    fn position(&self, path: &[Segment]) -> Option<(usize, usize)> {
+        let offset = self.span(path)?.start;
+        let before = &self.source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = offset - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        Some((line, column))
+    }
+
+    fn error(&mut self, path: &[Segment], message: String) {
+        self.diagnostics.push(Diagnostic {
+            file: self.file.clone(),
+            position: self.position(path),
+            message,
+        });
+    }
+}
+
+/// Checks everything `toml` deserialization cannot in the context `name` of `file`:
+/// missing or duplicate slots, empty hostnames, and inconsistent fan and protection settings.
+pub fn validate(file: &ConfigFile, name: &str, path: &Path, source: &str) -> Vec<Diagnostic> {
+    let base = if file.legacy {
+        vec![]
+    } else {
+        vec![Key("contexts"), Key(name)]
+    };
+    let mut v = Validator::new(path, source, base);
+    let Some(config) = file.contexts.get(name) else {
+        v.error(&[], format!("unknown context '{name}'"));
+        return v.diagnostics;
+    };
+
+    if config.ssh_username.trim().is_empty() {
+        v.error(&[Key("ssh_username")], "ssh_username is empty".to_owned());
+    }
+
+    if !config.cluster.ip_address.is_empty() && parse_ip(&config.cluster.ip_address).is_none() {
+        v.error(
+            &[Key("cluster"), Key("ip_address")],
+            format!(
+                "'{}' is not an IPv4 or IPv6 address",
+                config.cluster.ip_address
+            ),
+        );
+    }
+
+    validate_ssh(&mut v, &[Key("cluster"), Key("ssh")], &config.cluster.ssh);
+
+    let nodes = &config.cluster.nodes;
+    if !nodes.iter().any(|n| n.slot_number == 1) {
+        v.error(
+            &[Key("cluster")],
+            "no controller node configured (a node with slot_number = 1 is required)".to_owned(),
+        );
+    }
+    for (i, node) in nodes.iter().enumerate() {
+        let node_path = [Key("cluster"), Key("nodes"), Index(i)];
+        if node.hostname.trim().is_empty() && node.ip_address.trim().is_empty() {
+            v.error(
+                &[node_path[0], node_path[1], node_path[2], Key("hostname")],
+                format!(
+                    "the node in slot {} has neither a hostname nor an ip_address",
+                    node.slot_number
+                ),
+            );
+        }
+        if !node.ip_address.is_empty() && parse_ip(&node.ip_address).is_none() {
+            v.error(
+                &[node_path[0], node_path[1], node_path[2], Key("ip_address")],
+                format!("'{}' is not an IPv4 or IPv6 address", node.ip_address),
+            );
+        }
+        validate_ssh(
+            &mut v,
+            &[node_path[0], node_path[1], node_path[2], Key("ssh")],
+            &node.ssh,
+        );
+        let slot_path = [node_path[0], node_path[1], node_path[2], Key("slot_number")];
+        if !(1..=SLOT_COUNT).contains(&node.slot_number) {
+            v.error(
+                &slot_path,
+                format!(
+                    "slot_number {} is out of range (1 to {SLOT_COUNT})",
+                    node.slot_number
+                ),
+            );
+        }
+        if let Some(first) = nodes[..i]
+            .iter()
+            .position(|n| n.slot_number == node.slot_number)
+        {
+            let message = match v.position(&[Key("cluster"), Key("nodes"), Index(first)]) {
+                Some((line, _)) => format!(
+                    "duplicate slot_number {} (already used by the node at line {line})",
+                    node.slot_number
+                ),
+                None => format!("duplicate slot_number {}", node.slot_number),
+            };
+            v.error(&slot_path, message);
+        }
+    }
+
+    for (group, members) in &config.cluster.groups {
+        for (i, member) in members.iter().enumerate() {
+            if !nodes
+                .iter()
+                .any(|n| n.hostname.eq_ignore_ascii_case(member))
+            {
+                v.error(
+                    &[Key("cluster"), Key("groups"), Key(group), Index(i)],
+                    format!("group '{group}' lists '{member}', which is not a node hostname"),
+                );
+            }
+        }
+    }
+
+    let fan = &config.fan;
+    if fan.interval_secs == 0 {
+        v.error(
+            &[Key("fan"), Key("interval_secs")],
+            "interval_secs must be greater than 0".to_owned(),
+        );
+    }
+    if fan.hysteresis < 0.0 {
+        v.error(
+            &[Key("fan"), Key("hysteresis")],
+            "hysteresis must not be negative".to_owned(),
+        );
+    }
+    for (i, point) in fan.curve.iter().enumerate() {
+        if point.state < 0 {
+            v.error(
+                &[Key("fan"), Key("curve"), Index(i), Key("state")],
+                format!("fan curve state must not be negative, got {}", point.state),
+            );
+        }
+    }
+    for (i, window) in fan.schedule.windows.iter().enumerate() {
+        for (key, value) in [("start", &window.start), ("end", &window.end)] {
+            if NaiveTime::parse_from_str(value, "%H:%M").is_err() {
+                v.error(
+                    &[
+                        Key("fan"),
+                        Key("schedule"),
+                        Key("windows"),
+                        Index(i),
+                        Key(key),
+                    ],
+                    format!("invalid quiet hours {key} '{value}', expected HH:MM"),
+                );
+            }
+        }
+        if window.max_state < 0 {
+            v.error(
+                &[
+                    Key("fan"),
+                    Key("schedule"),
+                    Key("windows"),
+                    Index(i),
+                    Key("max_state"),
+                ],
+                format!("max_state must not be negative, got {}", window.max_state),
+            );
+        }
+    }
+
+    let protection = &config.protection;
+    for (key, thresholds) in [
+        ("cm4", &protection.cm4),
+        ("cm5", &protection.cm5),
+        ("lpi3h", &protection.lpi3h),
+    ] {
+        if thresholds.warning >= thresholds.critical {
+            v.error(
+                &[Key("protection"), Key(key), Key("warning")],
+                format!(
+                    "{key} warning threshold ({}) must be lower than the critical one ({})",
+                    thresholds.warning, thresholds.critical
+                ),
+            );
+        }
+    }
+
+    v.diagnostics
+}
+
+fn validate_ssh(v: &mut Validator, path: &[Segment], settings: &SshSettings) {
+    let at = |key| {
+        let mut full = path.to_vec();
+        full.push(Key(key));
+        full
+    };
+    if settings.port == Some(0) {
+        v.error(&at("port"), "ssh port must not be 0".to_owned());
+    }
+    if settings.connect_timeout_secs == Some(0) {
+        v.error(
+            &at("connect_timeout_secs"),
+            "connect_timeout_secs must be greater than 0".to_owned(),
+        );
+    }
+    if let Some(identity_file) = &settings.identity_file
+        && !expand_tilde(identity_file).exists()
+    {
+        v.error(
+            &at("identity_file"),
+            format!("identity_file {} does not exist", identity_file.display()),
+        );
+    }
+    if settings
+        .proxy_jump
+        .as_deref()
+        .is_some_and(|j| j.trim().is_empty())
+    {
+        v.error(&at("proxy_jump"), "proxy_jump is empty".to_owned());
+    }
+}
+
+/// Replaces a leading `~` with the user's home directory.
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_owned(),
+    }
+}
+
+/// Parses an IP address, ignoring the zone index of link-local IPv6 addresses (`fe80::1%eth0`).
+fn parse_ip(address: &str) -> Option<IpAddr> {
+    address.split('%').next()?.parse().ok()
+}
+
+/// Validates every context of `file`, and that `current_context` names one of them.
+pub fn validate_file(file: &ConfigFile, path: &Path, source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    if let Some(current) = &file.current_context
+        && !file.contexts.contains_key(current)
+    {
+        let mut v = Validator::new(path, source, vec![]);
+        v.error(
+            &[Key("current_context")],
+            format!("current_context '{current}' is not defined under [contexts]"),
+        );
+        diagnostics.extend(v.diagnostics);
+    }
+    for name in file.contexts.keys() {
+        diagnostics.extend(validate(file, name, path, source));
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostics(content: &str, context: &str) -> Vec<String> {
+        let path = Path::new("cluster.toml");
+        let file = parse(path, content).unwrap();
+        validate(&file, context, path, content)
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect()
+    }
+
+    const LEGACY: &str = r#"ssh_username = "pi"
+
+[cluster]
+_ip_address = "192.168.1.300"
+
+[[cluster.nodes]]
+hostname = "node-02"
+model = "CM5"
+slot_number = 2
+
+[[cluster.nodes]]
+hostname = "node-03"
+_ip_address = "10.0.0.x"
+model = "CM5"
+slot_number = 2
+"#;
+
+    #[test]
+    fn legacy_layout_positions() {
+        assert_eq!(
+            diagnostics(LEGACY, DEFAULT_CONTEXT),
+            [
+                "cluster.toml:4:15: '192.168.1.300' is not an IPv4 or IPv6 address",
+                "cluster.toml:3:1: no controller node configured (a node with slot_number = 1 is required)",
+                "cluster.toml:13:15: '10.0.0.x' is not an IPv4 or IPv6 address",
+                "cluster.toml:15:15: duplicate slot_number 2 (already used by the node at line 6)",
+            ]
+        );
+    }
+
+    #[test]
+    fn contexts_layout_positions() {
+        let content = r#"current_context = "lab"
+
+[contexts.home]
+ssh_username = "pi"
+
+[[contexts.home.cluster.nodes]]
+hostname = "controller"
+model = "CM4"
+slot_number = 1
+
+[contexts.lab]
+ssh_username = "pi"
+
+[contexts.lab.cluster]
+_ip_address = "not-an-ip"
+
+[[contexts.lab.cluster.nodes]]
+hostname = "node-02"
+model = "CM5"
+slot_number = 2
+
+[[contexts.lab.cluster.nodes]]
+hostname = "node-02b"
+model = "CM5"
+  slot_number = 2
+"#;
+        assert!(diagnostics(content, "home").is_empty());
+        assert_eq!(
+            diagnostics(content, "lab"),
+            [
+                "cluster.toml:15:15: 'not-an-ip' is not an IPv4 or IPv6 address",
+                "cluster.toml:14:1: no controller node configured (a node with slot_number = 1 is required)",
+                "cluster.toml:25:17: duplicate slot_number 2 (already used by the node at line 17)",
+            ]
+        );
+    }
+}
@@ -1,209 +1,205 @@
-use std::{fmt::Display, io::Read};
-
+use std::time::Duration;
+
+use crate::{
+    cli::{Cli, Command, ConfigCommand, FanCommand, NodeCommand, PowerCommand},
+    commands::{
+        calibrate, collect, config as config_cmd, dashboard, discover, exec, fan, fan_auto,
+        identify, inventory, power, protect, transfer, trust, watch,
+    },
+};
 use clap::Parser;
-use serde_derive::{Deserialize, Serialize};
-
-use crate::commands::{fan, power};
 
+mod cli;
 mod commands;
+mod config;
+mod output;
+mod selector;
+mod ssh;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Config {
-    ssh_username: String,
-    cluster: Cluster,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Cluster {
-    _ip_address: String,
-    nodes: Vec<Node>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Node {
-    _ip_address: String,
-    hostname: String,
-    model: Model,
-    slot_number: i32,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-enum Model {
-    CM5,
-    CM4,
-    LPI3H,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Config {
-            ssh_username: "".to_owned(),
-            cluster: Cluster {
-                _ip_address: "".to_owned(),
-                nodes: vec![
-                    Node {
-                        _ip_address: "".to_owned(),
-                        hostname: "".to_owned(),
-                        model: Model::LPI3H,
-                        slot_number: 1,
-                    },
-                    Node {
-                        _ip_address: "".to_owned(),
-                        hostname: "".to_owned(),
-                        model: Model::CM4,
-                        slot_number: 2,
-                    },
-                    Node {
-                        _ip_address: "".to_owned(),
-                        hostname: "".to_owned(),
-                        model: Model::CM4,
-                        slot_number: 3,
-                    },
-                    Node {
-                        _ip_address: "".to_owned(),
-                        hostname: "".to_owned(),
-                        model: Model::CM5,
-                        slot_number: 5,
-                    },
-                    Node {
-                        _ip_address: "".to_owned(),
-                        hostname: "".to_owned(),
-                        model: Model::CM5,
-                        slot_number: 6,
-                    },
-                    Node {
-                        _ip_address: "".to_owned(),
-                        hostname: "".to_owned(),
-                        model: Model::CM5,
-                        slot_number: 7,
-                    },
-                ],
-            },
-        }
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let args = Cli::parse();
+    let config_file = match &args.config {
+        Some(path) => path.clone(),
+        None => confy::get_configuration_file_path("nanocluster_control", "nanocluster_control")?,
+    };
+    if let Command::Config(ConfigCommand::Init) | Command::Init = args.command {
+        // A file that fails to parse is reported rather than overwritten, it may hold
+        // other contexts.
+        let existing = if config_file.exists() {
+            Some(config::load(&config_file)?.0)
+        } else {
+            None
+        };
+        return Ok(config_cmd::init(&config_file, existing, args.context.as_deref()).await?);
     }
-}
-
-#[derive(Parser)]
-struct Cli {
-    #[clap(value_enum)]
-    command: Command,
-
-    /// Node number to operate on, or 'all' for all nodes
-    #[clap(long = "node", value_parser = parse_node_selector, default_value = "all")]
-    node: NodeSelector,
-
-    /// Enable or disable the auto fan speed mode that comes with the LPI3H
-    #[clap(long = "fan-mode", value_enum, default_value = "disabled")]
-    fan_mode: FanMode,
-
-    /// Manually set the fan speed. Requires the fan_mode to be set at disabled
-    #[clap(long = "fan-speed", value_parser = parse_fan_speed, default_value = "4")]
-    fan_speed: FanSpeed,
-}
-
-#[derive(clap::ValueEnum, Clone, Debug)]
-enum FanMode {
-    Disabled,
-    Enabled,
-}
-
-impl Display for FanMode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            FanMode::Disabled => write!(f, "disabled"),
-            FanMode::Enabled => write!(f, "enabled"),
+    let format = args.output_format();
+    let (mut file, config_file_content) = config::load(&config_file)?;
+
+    // Commands working on the whole file rather than on one cluster.
+    match &args.command {
+        Command::Config(ConfigCommand::Validate) | Command::Validate => {
+            let diagnostics = config::validate_file(&file, &config_file, &config_file_content);
+            return Ok(config_cmd::validate(&config_file, &diagnostics, format)?);
+        }
+        Command::Config(ConfigCommand::Contexts) | Command::Contexts => {
+            return Ok(config_cmd::list_contexts(&file, format)?);
+        }
+        Command::Config(ConfigCommand::UseContext { name }) => {
+            return Ok(config_cmd::use_context(
+                &config_file,
+                &config_file_content,
+                &file,
+                Some(name),
+            )?);
+        }
+        Command::LegacyUseContext => {
+            return Ok(config_cmd::use_context(
+                &config_file,
+                &config_file_content,
+                &file,
+                args.context.as_deref(),
+            )?);
         }
+        _ => {}
     }
-}
-
-#[derive(Debug, Clone)]
-struct FanSpeed(i32);
 
-impl Display for FanSpeed {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+    let context = file.select(args.context.as_deref())?.0.to_owned();
+    log::debug!("Using context {context} of {}", config_file.display());
+    let diagnostics = config::validate(&file, &context, &config_file, &config_file_content);
+    let mut config = file
+        .contexts
+        .remove(&context)
+        .expect("selected context exists");
+    config.known_hosts_file = Some(config::known_hosts_file(&config_file));
+    config.known_hosts_override = args.known_hosts;
+    let config = &config;
+
+    if args.command.is_power_action() && !diagnostics.is_empty() {
+        for diagnostic in &diagnostics {
+            eprintln!("{diagnostic}");
+        }
+        return Err(anyhow::anyhow!(
+            "Refusing to run power actions against an invalid configuration"
+        )
+        .into());
     }
-}
-
-fn parse_fan_speed(s: &str) -> Result<FanSpeed, String> {
-    let speed = s
-        .parse::<i32>()
-        .map_err(|_| format!("Invalid fan speed: {}", s))?;
-    if speed < 0 || speed > 4 {
-        Err(format!("Fan speed must be between 0 and 4, got {}", speed))
-    } else {
-        Ok(FanSpeed(speed))
+    for diagnostic in &diagnostics {
+        log::warn!("{diagnostic}");
     }
-}
-
-#[derive(Debug, Clone)]
-enum NodeSelector {
-    All,
-    Number(i32),
-}
 
-fn parse_node_selector(s: &str) -> Result<NodeSelector, String> {
-    if s.eq_ignore_ascii_case("all") {
-        Ok(NodeSelector::All)
-    } else {
-        s.parse::<i32>()
-            .map(NodeSelector::Number)
-            .map_err(|_| format!("Invalid node selector: {}", s))
+    if let (Some(name), None) = (args.command.requires_node(), &args.node) {
+        return Err(anyhow::anyhow!(
+            "{name} requires --node <selector>, use --node all to select every node"
+        )
+        .into());
     }
-}
-
-#[derive(clap::ValueEnum, Clone, Debug)]
-enum Command {
-    SHUTDOWN,
-    BOOT,
-    STATUS,
-    FANMODE,
-    FANSPEED,
-}
-
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::init();
-    let args = Cli::parse();
-    let config: Config = confy::load("nanocluster_control", "nanocluster_control")?;
-    let config_file =
-        confy::get_configuration_file_path("nanocluster_control", "nanocluster_control")?;
-    let mut config_file_content = String::new();
-
-    std::fs::File::open(&config_file)
-        .expect("Failed to open toml configuration file.")
-        .read_to_string(&mut config_file_content)
-        .expect("Failed to read toml configuration file.");
-
-    let node_number = match args.node {
-        NodeSelector::All => None,
-        NodeSelector::Number(n) => Some(n),
+    let nodes = match &args.node {
+        Some(selector) => selector.resolve(config)?,
+        None => config.cluster.nodes.iter().collect(),
     };
 
     match args.command {
-        Command::SHUTDOWN => {
-            if node_number != None {
-                power::shutdown_single_node(&config, node_number.unwrap()).await
-            } else {
-                power::shutdown_all_nodes(&config).await
-            }
+        Command::Power(PowerCommand::On) | Command::Boot => {
+            power::boot_nodes(config, &nodes).await.print(format)?
         }
-        Command::BOOT => {
-            if node_number != None {
-                power::boot_single_node(&config, node_number.unwrap()).await
+        Command::Power(PowerCommand::Off) | Command::Shutdown => {
+            power::shutdown_nodes(config, &nodes).await.print(format)?
+        }
+        Command::Power(PowerCommand::Cycle) => {
+            power::cycle_nodes(config, &nodes).await.print(format)?
+        }
+        Command::Power(PowerCommand::Reboot) => {
+            power::reboot_nodes(config, &nodes).await.print(format)?
+        }
+        Command::Power(PowerCommand::Status(status)) | Command::Status(status) => {
+            if status.watch {
+                let interval = Duration::from_secs(status.interval);
+                watch::watch_power_status(config, &nodes, interval, format).await?
             } else {
-                power::boot_all_nodes(&config).await
+                power::print_power_status(config, &nodes, format, status.details).await?
             }
         }
-        Command::STATUS => {
-            if node_number != None {
-                power::print_node_power_status(&config, node_number.unwrap()).await
-            } else {
-                power::print_cluster_power_status(&config).await
+        Command::Fan(FanCommand::Mode { mode }) | Command::LegacyFanMode { fan_mode: mode } => {
+            fan::fan_mode(config, &mode).await?;
+            fan::print_fan_setting(format, "mode", mode.to_string())?
+        }
+        Command::Fan(FanCommand::Speed { speed })
+        | Command::LegacyFanSpeed { fan_speed: speed } => {
+            fan::fan_speed(config, &speed).await?;
+            fan::print_fan_setting(format, "speed", speed.to_string())?
+        }
+        Command::Fan(FanCommand::Status) | Command::LegacyFanStatus => {
+            fan::print_fan_status(config, format).await?
+        }
+        Command::Fan(FanCommand::Auto) | Command::LegacyFanAuto => {
+            fan_auto::fan_auto(config, &nodes).await?
+        }
+        Command::Protect => protect::protect(config, &nodes).await?,
+        Command::Exec { parallel, command } => {
+            exec::exec(config, &nodes, &command, parallel as usize, format)
+                .await
+                .print(format)?
+        }
+        Command::Push(push_args) => transfer::push(config, &nodes, &push_args)
+            .await?
+            .print(format)?,
+        Command::Pull(pull_args) => transfer::pull(config, &nodes, &pull_args)
+            .await
+            .print(format)?,
+        Command::Collect { files, directory } => {
+            collect::collect(config, &context, &nodes, &files, &directory)
+                .await?
+                .print(format)?
+        }
+        Command::Discover => {
+            discover::discover(
+                config,
+                &config_file,
+                &config_file_content,
+                &context,
+                file.legacy,
+                format,
+            )
+            .await?
+        }
+        Command::Identify { duration } => {
+            identify::identify(config, &nodes, Duration::from_secs(duration))
+                .await
+                .print(format)?
+        }
+        Command::Calibrate(calibrate_args) => {
+            calibrate::calibrate(
+                config,
+                &config_file,
+                &config_file_content,
+                &context,
+                file.legacy,
+                &calibrate_args,
+            )
+            .await?
+        }
+        Command::Inventory => {
+            let inventory_file = config::inventory_file(&config_file);
+            inventory::inventory(config, &context, &nodes, &inventory_file, format).await?
+        }
+        Command::Dashboard { refresh } => {
+            dashboard::dashboard(config, Duration::from_secs(refresh)).await?
+        }
+        Command::Node(NodeCommand::List) => config_cmd::list_nodes(config, &nodes, format)?,
+        Command::Node(NodeCommand::Trust) | Command::Trust => {
+            for node in &nodes {
+                trust::trust(config, node).await?;
             }
         }
-        Command::FANMODE => fan::fan_mode(&config, &args.fan_mode).await?,
-        Command::FANSPEED => fan::fan_speed(&config, &args.fan_speed).await?,
+        Command::Config(_)
+        | Command::Validate
+        | Command::Init
+        | Command::Contexts
+        | Command::LegacyUseContext => {
+            unreachable!("handled before selecting a context")
+        }
     }
 
     Ok(())
@@ -0,0 +1,175 @@
+use serde::Serialize;
+use serde_derive::Serialize;
+
+use crate::config::Node;
+
+/// Format of command results, selected with `--output`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable text and aligned tables
+    #[default]
+    Table,
+    Json,
+    Yaml,
+    /// One header line, then one line per record
+    Csv,
+}
+
+/// A record that can be printed as one row of a table or of a CSV file.
+///
+/// The serialized fields are the JSON and YAML schema; `HEADERS` and `cells` are their
+/// flattened form, in the same order.
+pub trait Row: Serialize {
+    const HEADERS: &'static [&'static str];
+
+    fn cells(&self) -> Vec<String>;
+
+    /// Cells for `table`, when friendlier than the raw CSV values.
+    fn table_cells(&self) -> Vec<String> {
+        self.cells()
+    }
+}
+
+/// Prints a list of records, as a JSON/YAML array or as table/CSV rows.
+pub fn print_rows<T: Row>(format: OutputFormat, rows: &[T]) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Table => print_table(T::HEADERS, rows.iter().map(Row::table_cells).collect()),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(rows)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(rows)?),
+        OutputFormat::Csv => print_csv(T::HEADERS, rows.iter().map(Row::cells))?,
+    }
+    Ok(())
+}
+
+/// Prints a single record, as a JSON/YAML object or a one-row CSV. `text` prints the
+/// human readable form used for `table`.
+pub fn print_record<T: Row>(
+    format: OutputFormat,
+    record: &T,
+    text: impl FnOnce(&T),
+) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Table => text(record),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(record)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(record)?),
+        OutputFormat::Csv => print_csv(T::HEADERS, std::iter::once(record.cells()))?,
+    }
+    Ok(())
+}
+
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<String>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(headers.iter().map(|h| h.to_uppercase()).collect());
+    for row in rows {
+        line(row);
+    }
+}
+
+fn print_csv(headers: &[&str], rows: impl Iterator<Item = Vec<String>>) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    writer.write_record(headers)?;
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Joins list values into a single table or CSV cell.
+pub fn join_cell<I: IntoIterator<Item = S>, S: AsRef<str>>(values: I) -> String {
+    values
+        .into_iter()
+        .map(|v| v.as_ref().to_owned())
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Result of an action applied to a set of nodes, e.g. `power on`.
+#[derive(Debug, Serialize)]
+pub struct OperationReport {
+    pub action: String,
+    pub results: Vec<NodeResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeResult {
+    pub slot_number: i32,
+    pub hostname: String,
+    pub outcome: Outcome,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Ok,
+    Skipped,
+    Failed,
+}
+
+impl NodeResult {
+    pub fn new(node: &Node, outcome: Outcome, message: impl Into<String>) -> Self {
+        NodeResult {
+            slot_number: node.slot_number,
+            hostname: node.hostname.clone(),
+            outcome,
+            message: message.into(),
+        }
+    }
+}
+
+impl OperationReport {
+    pub fn failures(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|r| r.outcome == Outcome::Failed)
+            .count()
+    }
+
+    /// Prints the report, then fails if any node failed so the exit code reflects it.
+    pub fn print(&self, format: OutputFormat) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(self)?),
+            OutputFormat::Yaml => print!("{}", serde_yaml::to_string(self)?),
+            // Table and CSV rows carry the action so they stay self-describing.
+            OutputFormat::Table | OutputFormat::Csv => {
+                let rows = self.results.iter().map(|r| {
+                    vec![
+                        self.action.clone(),
+                        r.slot_number.to_string(),
+                        r.hostname.clone(),
+                        format!("{:?}", r.outcome).to_lowercase(),
+                        r.message.clone(),
+                    ]
+                });
+                let headers = ["action", "slot_number", "hostname", "outcome", "message"];
+                if format == OutputFormat::Csv {
+                    print_csv(&headers, rows)?;
+                } else {
+                    print_table(&headers, rows.collect());
+                }
+            }
+        }
+        match self.failures() {
+            0 => Ok(()),
+            failed => anyhow::bail!(
+                "{} failed on {failed} of {} node(s)",
+                self.action,
+                self.results.len()
+            ),
+        }
+    }
+}
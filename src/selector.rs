@@ -0,0 +1,280 @@
+use std::fmt::Display;
+
+use crate::config::{Config, Model, Node};
+
+/// Set of nodes given on the command line, e.g. `2,3,5-7`, `node-03`, `model=CM5`,
+/// `label=gpu`, `group=storage` or `all,!4`.
+///
+/// Terms are separated by commas. The selection is the union of the positive terms (every
+/// node when there are none) minus the nodes matched by terms prefixed with `!`.
+#[derive(Debug, Clone)]
+pub struct NodeSelector {
+    source: String,
+    terms: Vec<(bool, Term)>,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    All,
+    Slot(i32),
+    Range(i32, i32),
+    Hostname(String),
+    Model(Model),
+    /// `label=key` matches any value, `label=key=value` only that value.
+    Label(String, Option<String>),
+    Group(String),
+}
+
+impl Display for NodeSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl NodeSelector {
+    /// Resolves the selector against the configured nodes, in slot order.
+    pub fn resolve<'a>(&self, config: &'a Config) -> anyhow::Result<Vec<&'a Node>> {
+        let mut included = Vec::new();
+        let mut excluded = Vec::new();
+        for (negated, term) in &self.terms {
+            let matched = term.matches(config)?;
+            // An exclusion that matches nothing already holds, e.g. `all,!4` with slot 4 empty.
+            if matched.is_empty() && !matches!(term, Term::All) {
+                if *negated {
+                    log::debug!("Nothing to exclude: {}", term.unmatched(config));
+                } else {
+                    return Err(term.unmatched(config));
+                }
+            }
+            if *negated {
+                excluded.extend(matched);
+            } else {
+                included.extend(matched);
+            }
+        }
+        let positive = self.terms.iter().any(|(negated, _)| !negated);
+
+        let mut nodes: Vec<&Node> = config
+            .cluster
+            .nodes
+            .iter()
+            .filter(|n| !positive || included.contains(&n.slot_number))
+            .filter(|n| !excluded.contains(&n.slot_number))
+            .collect();
+        nodes.sort_by_key(|n| n.slot_number);
+        if nodes.is_empty() {
+            anyhow::bail!("Node selector '{}' matches no node", self.source);
+        }
+        Ok(nodes)
+    }
+}
+
+impl Term {
+    /// Slot numbers of the nodes matched by this term. An unknown group is an error rather
+    /// than an empty match, so typos do not go unnoticed.
+    fn matches(&self, config: &Config) -> anyhow::Result<Vec<i32>> {
+        let nodes = &config.cluster.nodes;
+        let slots = |filter: &dyn Fn(&Node) -> bool| -> Vec<i32> {
+            nodes
+                .iter()
+                .filter(|n| filter(n))
+                .map(|n| n.slot_number)
+                .collect()
+        };
+        let matched = match self {
+            Term::All => slots(&|_| true),
+            Term::Slot(slot) => slots(&|n| n.slot_number == *slot),
+            Term::Range(start, end) => slots(&|n| (*start..=*end).contains(&n.slot_number)),
+            Term::Hostname(hostname) => slots(&|n| n.hostname.eq_ignore_ascii_case(hostname)),
+            Term::Model(model) => slots(&|n| n.model == *model),
+            Term::Label(key, value) => slots(&|n| match (n.labels.get(key), value) {
+                (Some(actual), Some(wanted)) => actual == wanted,
+                (Some(_), None) => true,
+                (None, _) => false,
+            }),
+            Term::Group(group) => {
+                let members = config.cluster.groups.get(group).ok_or_else(|| {
+                    let known: Vec<&str> =
+                        config.cluster.groups.keys().map(|k| k.as_str()).collect();
+                    anyhow::anyhow!(
+                        "Unknown group '{group}', known groups are: {}",
+                        known.join(", ")
+                    )
+                })?;
+                slots(&|n| members.iter().any(|m| m.eq_ignore_ascii_case(&n.hostname)))
+            }
+        };
+        Ok(matched)
+    }
+
+    /// Why this term matched no node; positive terms that match nothing are errors.
+    fn unmatched(&self, config: &Config) -> anyhow::Error {
+        match self {
+            Term::All => anyhow::anyhow!("No node is configured"),
+            Term::Slot(slot) => anyhow::anyhow!("No node in slot {slot}"),
+            Term::Range(start, end) => anyhow::anyhow!("No node in slots {start}-{end}"),
+            Term::Hostname(hostname) => {
+                let known: Vec<&str> = config
+                    .cluster
+                    .nodes
+                    .iter()
+                    .map(|n| n.hostname.as_str())
+                    .collect();
+                anyhow::anyhow!(
+                    "Unknown node '{hostname}', known nodes are: {}",
+                    known.join(", ")
+                )
+            }
+            Term::Model(model) => anyhow::anyhow!("No node of model {model:?}"),
+            Term::Label(key, Some(value)) => anyhow::anyhow!("No node has label {key}={value}"),
+            Term::Label(key, None) => anyhow::anyhow!("No node has label {key}"),
+            Term::Group(group) => anyhow::anyhow!("Group '{group}' has no node"),
+        }
+    }
+}
+
+/// Parses a `--node` value; see [`NodeSelector`] for the syntax.
+pub fn parse_node_selector(s: &str) -> Result<NodeSelector, String> {
+    let mut terms = Vec::new();
+    for raw in s.split(',') {
+        let raw = raw.trim();
+        let (negated, term) = match raw.strip_prefix('!') {
+            Some(term) => (true, term.trim()),
+            None => (false, raw),
+        };
+        if term.is_empty() {
+            return Err(format!("Empty term in node selector '{s}'"));
+        }
+        terms.push((negated, parse_term(term)?));
+    }
+    Ok(NodeSelector {
+        source: s.to_owned(),
+        terms,
+    })
+}
+
+fn parse_term(term: &str) -> Result<Term, String> {
+    if term.eq_ignore_ascii_case("all") {
+        return Ok(Term::All);
+    }
+    if let Ok(slot) = term.parse::<i32>() {
+        return Ok(Term::Slot(slot));
+    }
+    // Hostnames may contain dashes too, so only `number-number` is a range.
+    if let Some((start, end)) = term.split_once('-')
+        && let (Ok(start), Ok(end)) = (start.trim().parse::<i32>(), end.trim().parse::<i32>())
+    {
+        if start > end {
+            return Err(format!("Invalid slot range '{term}'"));
+        }
+        return Ok(Term::Range(start, end));
+    }
+    if let Some((key, value)) = term.split_once('=') {
+        return match key.trim().to_ascii_lowercase().as_str() {
+            "model" => value.parse().map(Term::Model),
+            "label" => match value.split_once('=') {
+                Some((key, value)) => Ok(Term::Label(key.to_owned(), Some(value.to_owned()))),
+                None if !value.is_empty() => Ok(Term::Label(value.to_owned(), None)),
+                None => Err(format!("Missing label name in '{term}'")),
+            },
+            "group" if !value.is_empty() => Ok(Term::Group(value.to_owned())),
+            "group" => Err(format!("Missing group name in '{term}'")),
+            other => Err(format!(
+                "Unknown selector '{other}=', expected model=, label= or group="
+            )),
+        };
+    }
+    Ok(Term::Hostname(term.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            ssh_username = "pi"
+
+            [cluster]
+            groups = { storage = ["node-03", "node-05"] }
+
+            [[cluster.nodes]]
+            hostname = "controller"
+            model = "CM4"
+            slot_number = 1
+
+            [[cluster.nodes]]
+            hostname = "node-03"
+            model = "CM5"
+            slot_number = 3
+            labels = { gpu = "hailo" }
+
+            [[cluster.nodes]]
+            hostname = "node-05"
+            model = "CM5"
+            slot_number = 5
+            labels = { gpu = "coral" }
+
+            [[cluster.nodes]]
+            hostname = "node-06"
+            model = "CM4"
+            slot_number = 6
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn resolve(selector: &str) -> anyhow::Result<Vec<i32>> {
+        let config = config();
+        let nodes = parse_node_selector(selector)
+            .map_err(anyhow::Error::msg)?
+            .resolve(&config)?;
+        Ok(nodes.iter().map(|n| n.slot_number).collect())
+    }
+
+    #[test]
+    fn ranges_and_hostnames_with_dashes() {
+        assert!(matches!(parse_term("5-7"), Ok(Term::Range(5, 7))));
+        assert!(matches!(parse_term("node-03"), Ok(Term::Hostname(h)) if h == "node-03"));
+        assert!(parse_term("7-5").is_err());
+        assert_eq!(resolve("5-7").unwrap(), [5, 6]);
+        assert_eq!(resolve("NODE-03").unwrap(), [3]);
+    }
+
+    #[test]
+    fn labels_with_and_without_value() {
+        assert!(matches!(
+            parse_term("label=gpu=hailo"),
+            Ok(Term::Label(k, Some(v))) if k == "gpu" && v == "hailo"
+        ));
+        assert_eq!(resolve("label=gpu").unwrap(), [3, 5]);
+        assert_eq!(resolve("label=gpu=coral").unwrap(), [5]);
+        assert!(parse_term("label=").is_err());
+    }
+
+    #[test]
+    fn empty_terms_are_rejected() {
+        assert!(parse_node_selector("2,,3").is_err());
+        assert!(parse_node_selector("").is_err());
+        assert!(parse_node_selector("!").is_err());
+    }
+
+    #[test]
+    fn exclusions_are_removed_from_the_union() {
+        assert_eq!(resolve("all,!1").unwrap(), [3, 5, 6]);
+        assert_eq!(resolve("!model=CM5").unwrap(), [1, 6]);
+        assert_eq!(resolve("group=storage,6,!5").unwrap(), [3, 6]);
+        assert!(resolve("all,!all").is_err());
+    }
+
+    #[test]
+    fn only_positive_terms_must_match() {
+        assert!(resolve("4").is_err());
+        assert!(resolve("3,node-09").is_err());
+        assert!(resolve("label=gpu=tpu").is_err());
+        assert!(resolve("group=compute").is_err());
+        assert_eq!(resolve("all,!4").unwrap(), [1, 3, 5, 6]);
+        assert_eq!(resolve("3,!label=missing").unwrap(), [3]);
+    }
+}
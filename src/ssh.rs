@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use openssh::{KnownHosts, Session, SessionBuilder};
+
+use crate::config::{self, Config, KnownHostsPolicy, Node, SshSettings};
+
+/// Opens a session to `address` with `settings`, jumping through `jump_host` if given.
+pub async fn connect_address(
+    settings: &SshSettings,
+    jump_host: Option<&str>,
+    address: &str,
+) -> anyhow::Result<Session> {
+    let mut builder = SessionBuilder::default();
+    if let Some(user) = &settings.user {
+        builder.user(user.clone());
+    }
+    if let Some(port) = settings.port {
+        builder.port(port);
+    }
+    if let Some(identity_file) = &settings.identity_file {
+        builder.keyfile(config::expand_tilde(identity_file));
+    }
+    if let Some(timeout) = settings.connect_timeout_secs {
+        builder.connect_timeout(Duration::from_secs(timeout));
+    }
+    builder.known_hosts_check(match settings.known_hosts.unwrap_or_default() {
+        KnownHostsPolicy::Strict => KnownHosts::Strict,
+        KnownHostsPolicy::Add => KnownHosts::Add,
+        KnownHostsPolicy::Accept => KnownHosts::Accept,
+    });
+    if let Some(known_hosts_file) = &settings.known_hosts_file {
+        if let Some(parent) = known_hosts_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        builder.user_known_hosts_file(known_hosts_file);
+    }
+    if let Some(jump_host) = jump_host {
+        builder.jump_hosts([jump_host]);
+    }
+    Ok(builder.connect(address).await?)
+}
+
+/// Opens a session to `node`, trying its addresses in the order given by its address policy.
+pub async fn connect(config: &Config, node: &Node) -> anyhow::Result<Session> {
+    let settings = config.ssh_settings(node);
+    let jump_host = jump_host(config, node, &settings)?;
+    let mut last_error = None;
+    for address in config.addresses(node) {
+        match connect_address(&settings, jump_host.as_deref(), address).await {
+            Ok(session) => return Ok(session),
+            Err(e) => {
+                log::debug!("Failed to connect to {address}: {e}");
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        anyhow::anyhow!(
+            "The node in slot {} has neither a hostname nor an ip_address",
+            node.slot_number
+        )
+    }))
+}
+
+/// Runs `script` through `sh -c` and returns its output, failing with its stderr when it
+/// exits with an error.
+pub async fn run(session: &Session, script: &str) -> anyhow::Result<String> {
+    let output = session.command("sh").arg("-c").arg(script).output().await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Remote command failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Quotes `value` as a single word for the remote shell.
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Opens a session to the controller node in slot 1.
+pub async fn connect_controller(config: &Config) -> anyhow::Result<Session> {
+    connect(config, config.controller()?).await
+}
+
+/// Opens a session to the host that `node` is reached through, if it has a `proxy_jump`.
+pub async fn connect_jump_host(config: &Config, node: &Node) -> anyhow::Result<Option<Session>> {
+    let settings = config.ssh_settings(node);
+    match jump_host(config, node, &settings)? {
+        None => Ok(None),
+        Some(_) if settings.proxy_jump.as_deref() == Some("controller") => {
+            Ok(Some(connect_controller(config).await?))
+        }
+        Some(destination) => Ok(Some(SessionBuilder::default().connect(destination).await?)),
+    }
+}
+
+/// Resolves the `proxy_jump` setting of `node` to a `[user@]host[:port]` destination.
+pub fn jump_host(
+    config: &Config,
+    node: &Node,
+    settings: &SshSettings,
+) -> anyhow::Result<Option<String>> {
+    match settings.proxy_jump.as_deref() {
+        Some("controller") => {
+            let controller = config.controller()?;
+            if controller.slot_number == node.slot_number {
+                return Ok(None);
+            }
+            let controller_settings = config.ssh_settings(controller);
+            let address = config
+                .addresses(controller)
+                .first()
+                .copied()
+                .ok_or_else(|| {
+                    anyhow::anyhow!("The controller has neither a hostname nor an ip_address")
+                })?;
+            // IPv6 literals need brackets once a port is appended.
+            let host = if address.contains(':') {
+                format!("[{address}]")
+            } else {
+                address.to_owned()
+            };
+            let user = controller_settings
+                .user
+                .map(|user| format!("{user}@"))
+                .unwrap_or_default();
+            let port = controller_settings
+                .port
+                .map(|port| format!(":{port}"))
+                .unwrap_or_default();
+            Ok(Some(format!("{user}{host}{port}")))
+        }
+        jump_host => Ok(jump_host.map(str::to_owned)),
+    }
+}